@@ -2,6 +2,8 @@ import optuna
 import subprocess
 from joblib import Parallel, delayed
 import json
+import os
+import tempfile
 
 d = None
 
@@ -13,16 +15,18 @@ def func(trial):
         else:
             d2[k] = v
 
-    with open("./data/params.json", 'w') as f:
-        json.dump(d2, f, indent=4)
-
-    proc = subprocess.run("./target/release/procon31-comp", shell=True, stdout=subprocess.PIPE, text=True);
-    res = proc.stdout
-    lis = res.split('\n')
-    lis = list(filter(lambda x: len(x) >= 1, lis))
-    print(lis)
-    x = lis[-1]
-    return int(x)
+    # 並列に走る試行同士でファイルを取り合わないよう, 試行ごとに別の一時ファイルを使う
+    with tempfile.TemporaryDirectory() as tmp:
+        params_path = os.path.join(tmp, "params.json")
+        result_path = os.path.join(tmp, "result.json")
+        with open(params_path, 'w') as f:
+            json.dump(d2, f, indent=4)
+
+        subprocess.run(["./target/release/procon31-comp", "simulate", "social_distance:" + params_path, "social_distance", result_path], stdout=subprocess.DEVNULL)
+        with open(result_path) as f:
+            res = json.load(f)
+    print(res["win"], res["draw"], res["loss"], res["diff_sum"])
+    return int(res["diff_sum"])
 
 def run():
     study = optuna.load_study(study_name="opt_study", storage="sqlite:///./opt_study.db")
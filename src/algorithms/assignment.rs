@@ -0,0 +1,255 @@
+use crate::field;
+use crate::simulator;
+
+use field::{Field, Point};
+use simulator::Act;
+use std::collections::{HashMap, HashSet};
+
+// 選べない組み合わせのコスト
+const FORBIDDEN: f64 = 1e12;
+// 停止が候補にないエージェントを止めるときのコスト
+const FALLBACK: f64 = 1e9;
+
+// 行 (エージェント) ごとに異なる列を選び, コストの合計を最小にする (行数 <= 列数).
+// ハンガリアン法で O(n^2 m)
+fn hungarian(cost: &[Vec<f64>]) -> Vec<usize> {
+    let n = cost.len();
+    let m = cost[0].len();
+    let mut u = vec![0.0; n + 1];
+    let mut v = vec![0.0; m + 1];
+    let mut p = vec![0; m + 1];
+    let mut way = vec![0; m + 1];
+    for i in 1..=n {
+        p[0] = i;
+        let mut j0 = 0;
+        let mut minv = vec![f64::INFINITY; m + 1];
+        let mut used = vec![false; m + 1];
+        loop {
+            used[j0] = true;
+            let i0 = p[j0];
+            let mut delta = f64::INFINITY;
+            let mut j1 = 0;
+            for j in 1..=m {
+                if used[j] {
+                    continue;
+                }
+                let cur = cost[i0 - 1][j - 1] - u[i0] - v[j];
+                if cur < minv[j] {
+                    minv[j] = cur;
+                    way[j] = j0;
+                }
+                if minv[j] < delta {
+                    delta = minv[j];
+                    j1 = j;
+                }
+            }
+            for j in 0..=m {
+                if used[j] {
+                    u[p[j]] += delta;
+                    v[j] -= delta;
+                } else {
+                    minv[j] -= delta;
+                }
+            }
+            j0 = j1;
+            if p[j0] == 0 {
+                break;
+            }
+        }
+        loop {
+            let j1 = way[j0];
+            p[j0] = p[j1];
+            j0 = j1;
+            if j0 == 0 {
+                break;
+            }
+        }
+    }
+    let mut res = vec![0; n];
+    for j in 1..=m {
+        if p[j] != 0 {
+            res[p[j] - 1] = j - 1;
+        }
+    }
+    res
+}
+
+fn target(act: &Act) -> Option<Point> {
+    match act {
+        Act::PutAct(pos) | Act::MoveAct(pos) | Act::RemoveAct(pos) => Some(*pos),
+        Act::StayAct => None,
+    }
+}
+
+// 停止は専用の列に置き, それ以外の手は対象のマスの列に置く
+fn solve_once(acts: &[HashMap<Act, f64>], forbidden: &HashSet<(usize, Act)>) -> Vec<Act> {
+    let n = acts.len();
+    let mut tiles = acts
+        .iter()
+        .flat_map(|hm| hm.keys().filter_map(target))
+        .collect::<Vec<_>>();
+    tiles.sort();
+    tiles.dedup();
+    let tile_idx = tiles
+        .iter()
+        .enumerate()
+        .map(|(i, pos)| (*pos, n + i))
+        .collect::<HashMap<_, _>>();
+
+    let mut cost = vec![vec![FORBIDDEN; n + tiles.len()]; n];
+    let mut choice = vec![vec![None; n + tiles.len()]; n];
+    for (id, hm) in acts.iter().enumerate() {
+        cost[id][id] = FALLBACK;
+        choice[id][id] = Some(Act::StayAct);
+        let mut items = hm.iter().collect::<Vec<_>>();
+        items.sort_by(|a, b| a.0.cmp(b.0));
+        for (act, value) in items {
+            if forbidden.contains(&(id, act.clone())) {
+                continue;
+            }
+            let col = match target(act) {
+                Some(pos) => tile_idx[&pos],
+                None => id,
+            };
+            if -value < cost[id][col] {
+                cost[id][col] = -value;
+                choice[id][col] = Some(act.clone());
+            }
+        }
+    }
+    if n == 0 {
+        return Vec::new();
+    }
+    hungarian(&cost)
+        .into_iter()
+        .enumerate()
+        .map(|(id, col)| {
+            if cost[id][col] >= FORBIDDEN {
+                Act::StayAct
+            } else {
+                choice[id][col].clone().unwrap_or(Act::StayAct)
+            }
+        })
+        .collect()
+}
+
+// 自分のエージェントがいるマスを対象にした手は, そのエージェントが移動で空けない限り無効になる.
+// 空ける側が循環していても無効. そのような手を選んだエージェントを返す
+fn violation(side: bool, acts: &[Act], field: &Field) -> Option<usize> {
+    let occupant = (0..acts.len())
+        .filter_map(|id| field.agent(side, id).map(|pos| (pos, id)))
+        .collect::<HashMap<_, _>>();
+    for id in 0..acts.len() {
+        let mut now = id;
+        let mut visited = HashSet::new();
+        visited.insert(id);
+        while let Some(next) = target(&acts[now]).and_then(|pos| occupant.get(&pos)) {
+            if *next == now {
+                break;
+            }
+            match acts[*next] {
+                Act::MoveAct(_) if !visited.contains(next) => {
+                    visited.insert(*next);
+                    now = *next;
+                }
+                _ => return Some(id),
+            }
+        }
+    }
+    None
+}
+
+// 各エージェントの手の評価値から, 対象のマスが重ならず, 移動の連鎖も成立する手の組で合計が最大のものを選ぶ.
+// 成立しない手を選んだら, その手を除いて解き直す
+pub fn assign(side: bool, acts: &[HashMap<Act, f64>], field: &Field) -> Vec<Act> {
+    let mut forbidden = HashSet::new();
+    loop {
+        let res = solve_once(acts, &forbidden);
+        match violation(side, &res, field) {
+            Some(id) => {
+                if !forbidden.insert((id, res[id].clone())) {
+                    return res;
+                }
+            }
+            None => return res,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(agents: &[Point]) -> Field {
+        let mut field = Field::from_points(&[&[1, 1, 1, 1]], agents.len());
+        for (id, pos) in agents.iter().enumerate() {
+            field.set_agent(false, id, Some(*pos));
+        }
+        field
+    }
+
+    fn candidates(items: &[(Act, f64)]) -> HashMap<Act, f64> {
+        items.iter().cloned().collect()
+    }
+
+    #[test]
+    fn same_tile_goes_to_higher_value() {
+        let field = field(&[Point::new(0, 0), Point::new(2, 0)]);
+        let to = Point::new(1, 0);
+        let acts = vec![
+            candidates(&[(Act::MoveAct(to), 1.0), (Act::StayAct, 0.0)]),
+            candidates(&[(Act::MoveAct(to), 2.0), (Act::StayAct, 0.0)]),
+        ];
+        assert_eq!(
+            assign(false, &acts, &field),
+            vec![Act::StayAct, Act::MoveAct(to)]
+        );
+    }
+
+    #[test]
+    fn move_onto_staying_agent_is_infeasible() {
+        let field = field(&[Point::new(0, 0), Point::new(1, 0)]);
+        // 停止以外に候補がないエージェントの上には移動できない
+        let acts = vec![
+            candidates(&[(Act::MoveAct(Point::new(1, 0)), 5.0)]),
+            candidates(&[(Act::StayAct, 0.0)]),
+        ];
+        assert_eq!(
+            assign(false, &acts, &field),
+            vec![Act::StayAct, Act::StayAct]
+        );
+    }
+
+    #[test]
+    fn chain_into_vacated_tiles() {
+        let field = field(&[Point::new(0, 0), Point::new(1, 0), Point::new(2, 0)]);
+        let acts = (0..3)
+            .map(|x| {
+                candidates(&[
+                    (Act::MoveAct(Point::new(x + 1, 0)), 1.0),
+                    (Act::StayAct, 0.0),
+                ])
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            assign(false, &acts, &field),
+            (0..3)
+                .map(|x| Act::MoveAct(Point::new(x + 1, 0)))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn swap_is_rejected() {
+        let field = field(&[Point::new(0, 0), Point::new(1, 0)]);
+        // 入れ替わりは循環になるので, どちらも止まる
+        let acts = vec![
+            candidates(&[(Act::MoveAct(Point::new(1, 0)), 5.0), (Act::StayAct, 0.0)]),
+            candidates(&[(Act::MoveAct(Point::new(0, 0)), 5.0), (Act::StayAct, 0.0)]),
+        ];
+        assert_eq!(
+            assign(false, &acts, &field),
+            vec![Act::StayAct, Act::StayAct]
+        );
+    }
+}
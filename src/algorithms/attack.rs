@@ -0,0 +1,150 @@
+use super::base;
+use super::pathfind;
+use crate::field;
+
+use field::{Field, Point, PointUsize, State};
+use std::collections::{HashSet, VecDeque};
+
+// 相手の陣地を開けられる相手の壁 (cut) と, それを除去しに行く計画
+#[derive(Clone, Debug)]
+pub struct Breach {
+    pub wall: Point,
+    // 除去すると囲いの外とつながる陣地のマス
+    pub region: Vec<Point>,
+    // region の陣地点の合計
+    pub value: i16,
+    // (除去しに行くエージェントの id, 除去できるまでのターン数)
+    pub agent: Option<(usize, usize)>,
+}
+
+impl Breach {
+    pub fn turns(&self) -> Option<usize> {
+        self.agent.map(|(_, t)| t)
+    }
+    pub fn efficiency(&self) -> f64 {
+        match self.turns() {
+            Some(t) => self.value as f64 / (t + 1) as f64,
+            None => 0.0,
+        }
+    }
+}
+
+// owner の壁で囲まれていないマス (盤外から 8 近傍でたどれるマス). removed の壁はないものとする
+fn open_tiles(owner: bool, field: &Field, removed: Option<Point>) -> Vec<Vec<bool>> {
+    let mut open = vec![vec![false; field.height()]; field.width()];
+    let blocked =
+        |pos: Point| Some(pos) != removed && field.tile(pos.usize()).state() == State::Wall(owner);
+    let mut que = VecDeque::new();
+    for (x, col) in open.iter_mut().enumerate() {
+        for (y, open) in col.iter_mut().enumerate() {
+            let pos = PointUsize::new(x, y).normal();
+            let border = x == 0 || y == 0 || x + 1 == field.width() || y + 1 == field.height();
+            if border && !blocked(pos) {
+                *open = true;
+                que.push_back(pos);
+            }
+        }
+    }
+    while let Some(pos) = que.pop_front() {
+        for nex in base::make_neighbors(pos, field) {
+            let u = nex.usize();
+            if !open[u.x][u.y] && !blocked(nex) {
+                open[u.x][u.y] = true;
+                que.push_back(nex);
+            }
+        }
+    }
+    open
+}
+
+// owner の陣地を囲っている壁のうち, 1 枚除去するだけで陣地が外とつながるものを列挙する.
+// 陣地は囲いが壊れても残るので, value はその後に踏んだり囲い直したりして奪える点の上限
+pub fn cuts(owner: bool, field: &Field) -> Vec<Breach> {
+    let open = open_tiles(owner, field, None);
+    let is_open = |pos: Point| {
+        let u = pos.usize();
+        open[u.x][u.y]
+    };
+    let mut res = Vec::new();
+    for x in 0..field.width() {
+        for y in 0..field.height() {
+            let wall = PointUsize::new(x, y).normal();
+            if field.tile(wall.usize()).state() != State::Wall(owner) {
+                continue;
+            }
+            let neighbors = base::make_neighbors(wall, field);
+            let border = neighbors.len() < 8;
+            let inner = neighbors
+                .iter()
+                .any(|p| !is_open(*p) && field.tile(p.usize()).state() == State::Position(owner));
+            if !inner || !(border || neighbors.iter().any(|p| is_open(*p))) {
+                continue;
+            }
+            let after = open_tiles(owner, field, Some(wall));
+            let region = (0..field.width())
+                .flat_map(|i| (0..field.height()).map(move |j| (i, j)))
+                .filter(|(i, j)| after[*i][*j] && !open[*i][*j])
+                .map(|(i, j)| PointUsize::new(i, j).normal())
+                .filter(|p| field.tile(p.usize()).state() == State::Position(owner))
+                .collect::<Vec<_>>();
+            let value = region
+                .iter()
+                .fold(0, |b, p| b + field.tile(p.usize()).point().abs() as i16);
+            if value > 0 {
+                res.push(Breach {
+                    wall,
+                    region,
+                    value,
+                    agent: None,
+                });
+            }
+        }
+    }
+    res
+}
+
+// side のエージェントで, 相手の陣地の cut を除去しに行く計画を最大 count 個立てる.
+// エージェント 1 人につき 1 つで, 同じ陣地を開ける cut は 1 つだけ選ぶ
+pub fn propose(side: bool, field: &Field, count: usize) -> Vec<Breach> {
+    let remain = field.final_turn().saturating_sub(field.now_turn()) as usize;
+    // 途中の相手の壁の除去も数えた, 各エージェントから cut を除去し終えるまでのターン数
+    let agents = (0..field.agent_count())
+        .filter_map(|id| {
+            let pos = field.agent(side, id)?;
+            Some((id, pathfind::distance_map(side, field, &[pos], remain + 1)))
+        })
+        .collect::<Vec<_>>();
+    let mut cand = Vec::new();
+    for breach in cuts(!side, field) {
+        for (id, map) in &agents {
+            if let Some(turns) = map.removal(breach.wall) {
+                let mut b = breach.clone();
+                b.agent = Some((*id, turns));
+                cand.push(b);
+            }
+        }
+    }
+    cand.sort_by(|a, b| {
+        b.efficiency()
+            .partial_cmp(&a.efficiency())
+            .unwrap()
+            .then(a.wall.cmp(&b.wall))
+    });
+
+    let mut used = HashSet::new();
+    let mut opened = HashSet::new();
+    let mut res = Vec::new();
+    for b in cand {
+        if res.len() >= count {
+            break;
+        }
+        let (id, _) = b.agent.unwrap();
+        if used.contains(&id) || b.region.iter().any(|p| opened.contains(p)) {
+            continue;
+        }
+        used.insert(id);
+        opened.extend(b.region.iter().cloned());
+        res.push(b);
+    }
+    res
+}
@@ -1,51 +1,71 @@
+use super::assignment;
+use super::context::SolveContext;
+use super::equilibrium::Equilibrium;
+use super::explain::Explanation;
 use crate::field;
 use crate::simulator;
 use field::{Field, Point, PointUsize};
 use ordered_float::OrderedFloat;
-use rand::Rng;
 use simulator::Act;
 use std::cmp::Reverse;
-use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::collections::{HashMap, HashSet};
 
 const PUT_BORDER: i8 = 0;
+// 最適応答の期待値を求めるときに平均戦略から引く手の組の数
+const BR_SAMPLES: usize = 16;
 
-pub trait Solver<'a> {
-    fn new(side: bool, field: &'a Field) -> Self;
-    fn field(&self) -> &Field;
+pub trait Solver {
     fn side(&self) -> bool;
-    fn solve(&mut self) -> Vec<Act>;
+    fn solve(&mut self, field: &Field, ctx: &mut SolveContext) -> Vec<Act>;
+    // ターンが進んだときに新しい盤面と, 前のターンに実際に適用された手を受け取る
+    fn update(&mut self, _field: &Field, _resolved: &[Vec<Act>]) {}
+    // 最後の solve で手を選んだ理由 (記録していないソルバーは None)
+    fn explain(&self) -> Option<&Explanation> {
+        None
+    }
 }
 
 pub trait EachEvalSolver {
-    fn eval(&self, id: usize, act: Act) -> Option<f64>;
+    fn eval(&self, field: &Field, id: usize, act: Act) -> Option<f64>;
 }
 
-pub fn solve<'a, T: Solver<'a> + EachEvalSolver>(solver: &T) -> Vec<Act> {
-    let field = solver.field();
+pub fn solve<T: Solver + EachEvalSolver>(
+    solver: &T,
+    field: &Field,
+    name: &str,
+) -> (Vec<Act>, Explanation) {
     let mut eval_scores = Vec::new();
     for id in 0..field.agent_count() {
         let mut ev = HashMap::new();
         let acts = make_acts(solver.side(), id, field);
         for act in acts {
-            if let Some(score) = solver.eval(id, act.clone()) {
+            if let Some(score) = solver.eval(field, id, act.clone()) {
                 ev.insert(act.clone(), score);
             }
         }
         eval_scores.push(ev);
     }
-    primal_dual(solver.side(), &eval_scores, &field)
+    let acts = assignment::assign(solver.side(), &eval_scores, &field);
+    let explanation = Explanation::from_scores(name, field, &eval_scores, &acts);
+    (acts, explanation)
 }
 
-pub fn solve_regret_matching<'a, T: Solver<'a> + EachEvalSolver>(
+// 両チームの評価値から均衡を求め, 相手の平均戦略への最適応答を手の割り当てで選ぶ.
+// opponent があれば相手の初期戦略と平均戦略の事前分布に使い, 均衡は両チームとも解く.
+// 割り当てに使った最適応答の期待利得も返す
+pub fn solve_regret_matching<T: Solver + EachEvalSolver, F: Fn(bool) -> T>(
     side_: bool,
-    field: &'a Field,
+    field: &Field,
+    ctx: &mut SolveContext,
     num_iter: usize,
-) -> Vec<Act> {
+    make_solver: F,
+    opponent: Option<&[HashMap<Act, f64>]>,
+) -> (Vec<Act>, Equilibrium, Vec<HashMap<Act, f64>>) {
     let solver = [false, true]
         .iter()
         .map(|side| {
-            let mut sol = T::new(*side, field);
-            sol.solve();
+            let mut sol = make_solver(*side);
+            sol.solve(field, ctx);
             sol
         })
         .collect::<Vec<_>>();
@@ -56,15 +76,20 @@ pub fn solve_regret_matching<'a, T: Solver<'a> + EachEvalSolver>(
             let mut ev = HashMap::new();
             let acts = make_acts(side, id, field);
             for act in acts {
-                if let Some(score) = solver[side as usize].eval(id, act.clone()) {
+                if let Some(score) = solver[side as usize].eval(field, id, act.clone()) {
                     ev.insert(act.clone(), score);
                 }
             }
             eval_scores[side as usize].push(ev);
         }
     }
-    let prob = regret_matching(side_, eval_scores, &field, num_iter);
-    primal_dual(side_, &prob, &field)
+    let mut eq = Equilibrium::new(&eval_scores);
+    if let Some(prob) = opponent {
+        eq.prior(!side_, prob, num_iter as f64);
+    }
+    eq.run(field, num_iter, ctx);
+    let values = eq.best_response(side_, field, BR_SAMPLES, &mut ctx.rng);
+    (assignment::assign(side_, &values, field), eq, values)
 }
 
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq)]
@@ -91,315 +116,6 @@ impl MaxOrdFloat {
     }
 }
 
-#[derive(Clone)]
-struct Edge {
-    to: usize,
-    cap: usize,
-    cost: f64,
-    rev: usize,
-    act: Option<Act>,
-}
-
-impl Edge {
-    fn new(to: usize, cap: usize, cost: f64, rev: usize, act: Option<Act>) -> Edge {
-        Edge {
-            to,
-            cap,
-            cost,
-            rev,
-            act,
-        }
-    }
-}
-
-struct FlowGraph {
-    n: usize,
-    edges: Vec<Vec<Edge>>,
-    edge_list: Vec<(usize, usize)>,
-}
-
-impl FlowGraph {
-    fn new(n: usize) -> FlowGraph {
-        FlowGraph {
-            n,
-            edges: vec![Vec::new(); n],
-            edge_list: Vec::new(),
-        }
-    }
-    fn add(&mut self, from: usize, to: usize, cap: usize, cost: f64, act: Option<Act>) {
-        let from_idx = self.edges[from].len();
-        let to_idx = self.edges[to].len();
-        self.edges[from].push(Edge::new(to, cap, cost, to_idx, act));
-        self.edges[to].push(Edge::new(from, 0, -cost, from_idx, None));
-        self.edge_list.push((from, from_idx));
-    }
-    fn solve(&mut self, s: usize, t: usize, flow_: usize) {
-        let mut flow = flow_;
-        let mut heap: BinaryHeap<(MinOrdFloat, usize)> = BinaryHeap::new();
-        let mut prev_v = vec![0; self.n];
-        let mut prev_e = vec![0; self.n];
-        while flow > 0 {
-            let mut min_cost = vec![1e18; self.n];
-            min_cost[s] = 0.0;
-            heap.push((MinOrdFloat::new(0.0), s));
-            while !heap.is_empty() {
-                let (dist, pos) = heap.pop().unwrap();
-                if min_cost[pos] != dist.raw() {
-                    continue;
-                }
-                for (i, ed) in self.edges[pos].iter().enumerate() {
-                    let nex = dist.raw() + ed.cost;
-                    if ed.cap > 0 && min_cost[ed.to] > nex {
-                        min_cost[ed.to] = nex;
-                        prev_v[ed.to] = pos;
-                        prev_e[ed.to] = i;
-                        heap.push((MinOrdFloat::new(min_cost[ed.to]), ed.to));
-                    }
-                }
-            }
-            assert_ne!(min_cost[t], 1e18);
-            let mut add_flow = usize::max_value();
-            let mut x = t;
-            while x != s {
-                add_flow = add_flow.min(self.edges[prev_v[x]][prev_e[x]].cap);
-                x = prev_v[x];
-            }
-            flow -= add_flow;
-            x = t;
-            while x != s {
-                let ed_rev = {
-                    let mut ed = &mut self.edges[prev_v[x]][prev_e[x]];
-                    ed.cap -= add_flow;
-                    ed.rev
-                };
-                self.edges[x][ed_rev].cap += add_flow;
-                x = prev_v[x];
-            }
-        }
-    }
-}
-
-fn tile_pos(side: bool, id: usize, act: &Act, field: &Field) -> Point {
-    match act {
-        Act::PutAct(pos) | Act::MoveAct(pos) | Act::RemoveAct(pos) => pos.clone(),
-        Act::StayAct => field.agent(side, id).unwrap(),
-    }
-}
-
-fn primal_dual(side: bool, acts: &Vec<HashMap<Act, f64>>, field: &Field) -> Vec<Act> {
-    let max_val = acts.iter().fold(0.0, |ma, item| {
-        *vec![
-            ma,
-            *item
-                .values()
-                .max_by(|a, b| a.partial_cmp(b).unwrap())
-                .expect("acts is empty"),
-        ]
-        .iter()
-        .max_by(|a, b| a.partial_cmp(b).unwrap())
-        .expect("max_by error")
-    });
-
-    let poses: Vec<Point> = acts
-        .iter()
-        .enumerate()
-        .fold(HashSet::new(), |hs, (id, hm)| {
-            let keys: HashSet<Point> = hm
-                .keys()
-                .map(|act| tile_pos(side, id, act, field))
-                .collect();
-            hs.union(&keys).cloned().collect()
-        })
-        .into_iter()
-        .collect();
-    let num_nodes = poses.len() + acts.len() + 2;
-    let agent_count = acts.len();
-    let source_idx = poses.len() + acts.len();
-    let sink_idx = poses.len() + acts.len() + 1;
-
-    let mut tile_idx_map = HashMap::new();
-    for (idx, pos) in poses.iter().enumerate() {
-        tile_idx_map.insert(pos, idx + agent_count);
-    }
-
-    let mut graph = FlowGraph::new(num_nodes);
-    for agent_idx in 0..agent_count {
-        for (act, value) in &acts[agent_idx] {
-            let pos = tile_pos(side, agent_idx, act, field);
-            let tile_idx = *tile_idx_map.get(&pos).expect("tile_idx not found error");
-            graph.add(agent_idx, tile_idx, 1, max_val - value, Some(act.clone()));
-        }
-        graph.add(source_idx, agent_idx, 1, 0.0, None);
-    }
-    for tile_idx_ in 0..poses.len() {
-        let tile_idx = agent_count + tile_idx_;
-        graph.add(tile_idx, sink_idx, 1, 0.0, None);
-    }
-    graph.solve(source_idx, sink_idx, agent_count);
-
-    let mut acts = vec![Act::StayAct; agent_count];
-
-    for (from, idx) in graph.edge_list {
-        let ed = &graph.edges[from][idx];
-        let to = ed.to;
-        if from < agent_count && agent_count <= to && to < source_idx {
-            if ed.cap != 1 {
-                acts[from] = ed.act.clone().expect("act is None");
-            }
-        }
-    }
-    acts
-}
-
-fn regret_matching(
-    side_: bool,
-    act_scores: Vec<Vec<HashMap<Act, f64>>>,
-    field: &Field,
-    num_iter: usize,
-) -> Vec<HashMap<Act, f64>> {
-    let mut rng = rand::thread_rng();
-    let agent_count = field.agent_count();
-
-    let calc_prob = |regret: &Vec<Vec<HashMap<Act, f64>>>| -> Vec<Vec<HashMap<Act, f64>>> {
-        let regret_sum: Vec<Vec<f64>> = regret
-            .iter()
-            .map(|v| {
-                v.iter()
-                    .map(|hm| hm.iter().fold(0.0, |sum, (_, val)| sum + val))
-                    .collect()
-            })
-            .collect();
-        regret
-            .iter()
-            .enumerate()
-            .map(|(side, v)| {
-                v.iter()
-                    .enumerate()
-                    .map(|(id, hm)| {
-                        hm.iter().fold(HashMap::new(), |mut new_hm, (act, val)| {
-                            new_hm.insert(
-                                act.clone(),
-                                if regret_sum[side][id] == 0.0 {
-                                    1.0 / hm.len() as f64
-                                } else {
-                                    val / regret_sum[side][id]
-                                },
-                            );
-                            new_hm
-                        })
-                    })
-                    .collect()
-            })
-            .collect()
-    };
-    let mut calc_acts = |regret: &Vec<Vec<HashMap<Act, f64>>>| -> Vec<Vec<Act>> {
-        let prob = calc_prob(regret);
-        let mut acts = vec![vec![Act::StayAct; agent_count]; 2];
-        for side in 0..2 {
-            for (id, hm) in prob[side].iter().enumerate() {
-                let per = rng.gen::<f64>();
-                let mut prob_sum = 0.0;
-                for (k, v) in hm {
-                    prob_sum += v;
-                    if per < prob_sum {
-                        acts[side][id] = k.clone();
-                        break;
-                    }
-                }
-            }
-        }
-        acts
-    };
-
-    let mut regret = act_scores
-        .iter()
-        .map(|v| {
-            v.iter()
-                .map(|hm| {
-                    hm.iter().fold(HashMap::new(), |mut new_hm, (act, _)| {
-                        new_hm.insert(act.clone(), 0.0);
-                        new_hm
-                    })
-                })
-                .collect::<Vec<_>>()
-        })
-        .collect::<Vec<_>>();
-
-    for t in 0..num_iter {
-        /*
-        let acts = if t == 0 {
-            vec![
-                primal_dual(false, &act_scores[0], field),
-                primal_dual(true, &act_scores[1], field),
-            ]
-        } else {
-            calc_acts(&regret).clone()
-        };
-         */
-        let acts = calc_acts(&regret).clone();
-        let mut act_values = vec![vec![0.0; agent_count]; 2];
-        let mut pos_agents: HashMap<Point, Vec<(usize, usize)>> = HashMap::new();
-        for side in 0..2 {
-            for (id, act) in acts[side].iter().enumerate() {
-                let pos = tile_pos(side != 0, id, act, field);
-                let score = act_scores[side][id].get(act).cloned().unwrap();
-                act_values[side][id] = score;
-                match pos_agents.get(&pos) {
-                    Some(_) => {
-                        pos_agents.get_mut(&pos).unwrap().push((side, id));
-                    }
-                    None => {
-                        pos_agents.insert(pos, vec![(side, id)]);
-                    }
-                }
-            }
-        }
-
-        let mut mut_acts = acts.clone();
-        let mut regret_sum = 0.0;
-
-        let now_val = {
-            let mut now_reg = 0.0;
-            let act_vec = simulator::act_list(&acts, field);
-            for (side_, id_, act_) in act_vec {
-                now_reg += act_scores[side_ as usize][id_][&act_];
-            }
-            now_reg
-        };
-
-        for side in 0..2 {
-            for (id, act) in acts[side].iter().enumerate() {
-                let hm = &act_scores[side][id];
-                for (nex_act, _) in hm {
-                    mut_acts[side][id] = nex_act.clone();
-                    let reg = {
-                        let mut now_reg = -now_val;
-                        let act_vec = simulator::act_list(&mut_acts, field);
-                        for (side_, id_, act_) in act_vec {
-                            now_reg += act_scores[side_ as usize][id_][&act_];
-                        }
-                        now_reg
-                    }
-                    .max(0.0);
-                    *regret[side][id].get_mut(nex_act).unwrap() += reg;
-                    regret_sum += reg;
-                    mut_acts[side][id] = act.clone();
-                }
-            }
-        }
-        println!("{}/{}: {}", t, num_iter, regret_sum);
-        let lm = calc_prob(&regret)[side_ as usize].clone();
-        for id in 0..agent_count {
-            println!(
-                "max: {}",
-                lm[id].iter().fold(0.0, |now: f64, (_, v)| now.max(*v))
-            );
-        }
-        println!("");
-    }
-    calc_prob(&regret)[side_ as usize].clone()
-}
-
 pub fn make_neighbors(pos: Point, field: &Field) -> Vec<Point> {
     (-1..2)
         .fold(Vec::new(), |v, x| {
@@ -488,3 +204,19 @@ pub fn point(side: bool, act: Act, field: &Field) -> Option<i8> {
         }
     }
 }
+
+// 両チームの手を適用した次のターンの盤面
+pub fn step(field: &Field, acts: &[Vec<Act>]) -> Field {
+    let mut sim = simulator::Simulator::new(field.clone());
+    for side in [false, true] {
+        for (id, act) in acts[side as usize].iter().enumerate() {
+            sim.set_act(side, id, act.clone());
+        }
+    }
+    sim.change_turn();
+    sim.get_field().clone()
+}
+
+pub fn diff(side: bool, field: &Field) -> i16 {
+    field.score(side).sum() - field.score(!side).sum()
+}
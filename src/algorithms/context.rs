@@ -0,0 +1,159 @@
+use crate::record::TurnRecord;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// 時計の代わりに反復回数で持ち時間を数える. 各ソルバーは 1 反復の重さ (およそのマイクロ秒) を
+// spend で申告し, 1 単位を step_sec 秒とみなすので, シードが同じなら計算機の速さによらず同じ手になる
+struct Budget {
+    steps: usize,
+    used: AtomicUsize,
+    step_sec: f64,
+}
+
+impl Budget {
+    fn remaining(&self) -> usize {
+        self.steps.saturating_sub(self.used.load(Ordering::Relaxed))
+    }
+}
+
+// ソルバーに渡す 1 ターン分の情報 (持ち時間, 中断フラグ, 乱数, それまでの棋譜)
+pub struct SolveContext<'a> {
+    pub rng: StdRng,
+    pub history: &'a [TurnRecord],
+    start: Instant,
+    deadline: Instant,
+    turn_sec: f64,
+    cancel: Arc<AtomicBool>,
+    budget: Option<Budget>,
+}
+
+impl<'a> SolveContext<'a> {
+    pub fn new(turn_sec: f64, seed: u64, history: &'a [TurnRecord]) -> SolveContext<'a> {
+        let start = Instant::now();
+        SolveContext {
+            rng: StdRng::seed_from_u64(seed),
+            history,
+            start,
+            deadline: start + Duration::from_secs_f64(turn_sec),
+            turn_sec,
+            cancel: Arc::new(AtomicBool::new(false)),
+            budget: None,
+        }
+    }
+    // 1 ターンの持ち時間を steps 単位の反復とする (再現できる実行用)
+    pub fn with_budget(mut self, steps: usize) -> SolveContext<'a> {
+        self.budget = Some(Budget {
+            steps,
+            used: AtomicUsize::new(0),
+            step_sec: self.turn_sec / steps.max(1) as f64,
+        });
+        self
+    }
+    pub fn with_cancel(mut self, cancel: Arc<AtomicBool>) -> SolveContext<'a> {
+        self.cancel = cancel;
+        self
+    }
+    pub fn cancel_handle(&self) -> Arc<AtomicBool> {
+        self.cancel.clone()
+    }
+    pub fn cancelled(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+    pub fn turn_sec(&self) -> f64 {
+        self.turn_sec
+    }
+    pub fn elapsed(&self) -> f64 {
+        self.start.elapsed().as_secs_f64()
+    }
+    pub fn remaining(&self) -> f64 {
+        if self.cancelled() {
+            return 0.0;
+        }
+        if let Some(budget) = &self.budget {
+            return budget.remaining() as f64 * budget.step_sec;
+        }
+        let now = Instant::now();
+        if now >= self.deadline {
+            0.0
+        } else {
+            (self.deadline - now).as_secs_f64()
+        }
+    }
+    // 反復回数で持ち時間を数えているか
+    pub fn budgeted(&self) -> bool {
+        self.budget.is_some()
+    }
+    // 持ち時間のうち使った割合 (0 から 1)
+    pub fn progress(&self) -> f64 {
+        if let Some(budget) = &self.budget {
+            let used = budget.used.load(Ordering::Relaxed) as f64;
+            return (used / budget.steps.max(1) as f64).min(1.0);
+        }
+        let total = (self.deadline - self.start).as_secs_f64();
+        if total <= 0.0 {
+            1.0
+        } else {
+            (self.elapsed() / total).min(1.0)
+        }
+    }
+    pub fn timed_out(&self) -> bool {
+        self.remaining() <= 0.0
+    }
+    // 重さ steps の反復を 1 回始める. 持ち時間が尽きていれば何もせず true を返す
+    pub fn spend(&self, steps: usize) -> bool {
+        if self.timed_out() {
+            return true;
+        }
+        if let Some(budget) = &self.budget {
+            budget.used.fetch_add(steps, Ordering::Relaxed);
+        }
+        false
+    }
+    // 残り時間のうち sec 秒だけを使う子のコンテキスト (中断フラグは共有する)
+    // 反復回数で数えている場合は, 子に渡した分をこの場で親の予算から引く
+    pub fn split(&mut self, sec: f64) -> SolveContext<'a> {
+        let sec = sec.min(self.remaining()).max(0.0);
+        let start = Instant::now();
+        let budget = self.budget.as_ref().map(|budget| {
+            let steps = ((sec / budget.step_sec) as usize).min(budget.remaining());
+            budget.used.fetch_add(steps, Ordering::Relaxed);
+            Budget {
+                steps,
+                used: AtomicUsize::new(0),
+                step_sec: budget.step_sec,
+            }
+        });
+        SolveContext {
+            rng: StdRng::seed_from_u64(self.rng.gen()),
+            history: self.history,
+            start,
+            deadline: start + Duration::from_secs_f64(sec),
+            turn_sec: self.turn_sec,
+            cancel: self.cancel.clone(),
+            budget,
+        }
+    }
+    // 並列に動かす子のコンテキスト. 持ち時間 sec 秒は子を作った時点から数え, 親の締め切りは越えない.
+    // 反復回数で数えている場合は子ごとに予算を持ち, 親の予算からは引かない (子の動く順番によらない)
+    pub fn child(&self, sec: f64, seed: u64) -> SolveContext<'a> {
+        let sec = sec.min(self.remaining()).max(0.0);
+        let start = Instant::now();
+        let budget = self.budget.as_ref().map(|budget| Budget {
+            steps: ((sec / budget.step_sec) as usize).min(budget.remaining()),
+            used: AtomicUsize::new(0),
+            step_sec: budget.step_sec,
+        });
+        SolveContext {
+            rng: StdRng::seed_from_u64(seed),
+            history: self.history,
+            start,
+            deadline: self.deadline.min(start + Duration::from_secs_f64(sec)),
+            turn_sec: self.turn_sec,
+            cancel: self.cancel.clone(),
+            budget,
+        }
+    }
+}
@@ -0,0 +1,242 @@
+use super::base;
+use super::context::SolveContext;
+use super::explain::Explanation;
+use super::pathfind::{self, PathRules, Step};
+use super::placement;
+use super::roles::Roles;
+use crate::field;
+use crate::simulator;
+
+use field::{Field, Point};
+use rand::seq::SliceRandom;
+use simulator::Act;
+use std::collections::{HashMap, HashSet};
+
+const HORIZON: usize = 5;
+const PER: f64 = 0.7;
+// 優先順位を変えて計画し直す回数の上限
+const RESTARTS: usize = 8;
+
+// (マス, 時刻) の予約表. 時刻 t の予約はそのマスに t ターン後にいるか, そこへ手を打っていること
+#[derive(Default)]
+pub struct Reservations {
+    slots: HashMap<(Point, usize), usize>,
+    // 既に誰かの計画で得点を数えたマス
+    counted: HashSet<Point>,
+}
+
+impl Reservations {
+    pub fn is_free(&self, id: usize, pos: Point, t: usize) -> bool {
+        self.slots.get(&(pos, t)).map_or(true, |x| *x == id)
+    }
+    pub fn counted(&self, pos: Point) -> bool {
+        self.counted.contains(&pos)
+    }
+    fn reserve(&mut self, id: usize, pos: Point, t: usize) {
+        self.slots.entry((pos, t)).or_insert(id);
+    }
+    // start から path の通りに進み, 終わったらその場に留まるものとして horizon まで予約する
+    pub fn reserve_path(&mut self, id: usize, start: Point, path: &[Step], horizon: usize) {
+        let mut now = start;
+        let mut t = 0;
+        self.reserve(id, now, t);
+        for step in path {
+            let end = (t + step.turns).min(horizon);
+            // 除去している間は元のマスに留まる
+            for k in t + 1..=end {
+                self.reserve(id, step.to, k);
+                if k < end {
+                    self.reserve(id, now, k);
+                }
+            }
+            self.counted.insert(step.to);
+            now = step.to;
+            t = end;
+        }
+        for k in t..=horizon {
+            self.reserve(id, now, k);
+        }
+    }
+    // t から step を打ったとき, その間に他のエージェントとぶつからないか
+    fn allows(&self, id: usize, now: Point, step: &Step, t: usize, end: usize) -> bool {
+        (t..=end).all(|k| self.is_free(id, step.to, k))
+            && (t + 1..end).all(|k| self.is_free(id, now, k))
+    }
+    fn rest_free(&self, id: usize, pos: Point, t: usize, horizon: usize) -> bool {
+        (t..=horizon).all(|k| self.is_free(id, pos, k))
+    }
+}
+
+// 予約と衝突せず, 予約済みの得点を数え直さない経路を探すための制限.
+// 得点は役割に合わせて増減する
+struct Rules<'a> {
+    res: &'a Reservations,
+    roles: &'a Roles,
+    id: usize,
+    horizon: usize,
+}
+
+impl<'a> PathRules for Rules<'a> {
+    fn blocked(&self, pos: Point, step: &Step, t: usize, nt: usize) -> bool {
+        !self.res.allows(self.id, pos, step, t, nt)
+    }
+    fn value(&self, pos: Point, to: Point, point: f64) -> f64 {
+        if self.res.counted(to) {
+            0.0
+        } else {
+            self.roles.adjust(self.id, pos, to, point)
+        }
+    }
+    fn can_stop(&self, pos: Point, nt: usize) -> bool {
+        self.res.rest_free(self.id, pos, nt, self.horizon)
+    }
+}
+
+fn plan_agent(
+    side: bool,
+    field: &Field,
+    res: &Reservations,
+    roles: &Roles,
+    id: usize,
+    start: Point,
+    horizon: usize,
+) -> (f64, Vec<Step>) {
+    let rules = Rules {
+        res,
+        roles,
+        id,
+        horizon,
+    };
+    pathfind::best_path(side, field, start, horizon, PER, &rules)
+}
+
+// チーム全体の計画. paths[id] は配置済みのエージェントの経路 (未配置なら空)
+pub struct Plan {
+    pub order: Vec<usize>,
+    pub paths: Vec<Vec<Step>>,
+    pub scores: Vec<f64>,
+}
+
+impl Plan {
+    pub fn total(&self) -> f64 {
+        self.scores.iter().sum()
+    }
+}
+
+// order の順に経路を決め, 決めた経路を予約していく (優先順位付き計画)
+pub fn plan(side: bool, field: &Field, roles: &Roles, order: &[usize], horizon: usize) -> Plan {
+    let mut res = Reservations::default();
+    // 動く前の位置は全員分を先に押さえ, 相手は次のターンも留まるものとする
+    for id in 0..field.agent_count() {
+        if let Some(pos) = field.agent(side, id) {
+            res.reserve(id, pos, 0);
+            res.reserve(id, pos, 1);
+        }
+        if let Some(pos) = field.agent(!side, id) {
+            res.reserve(usize::MAX, pos, 0);
+            res.reserve(usize::MAX, pos, 1);
+        }
+    }
+    let mut paths = vec![Vec::new(); field.agent_count()];
+    let mut scores = vec![0.0; field.agent_count()];
+    for id in order {
+        let start = match field.agent(side, *id) {
+            Some(pos) => pos,
+            None => continue,
+        };
+        let (score, path) = plan_agent(side, field, &res, roles, *id, start, horizon);
+        res.reserve_path(*id, start, &path, horizon);
+        paths[*id] = path;
+        scores[*id] = score;
+    }
+    Plan {
+        order: order.to_vec(),
+        paths,
+        scores,
+    }
+}
+
+// 予約表を使って自分のエージェントの経路が重ならないように計画する
+pub struct Cooperative {
+    side: bool,
+    roles: Roles,
+    explanation: Option<Explanation>,
+}
+
+impl Cooperative {
+    pub fn new(side: bool) -> Cooperative {
+        Cooperative {
+            side,
+            roles: Roles::new(side),
+            explanation: None,
+        }
+    }
+}
+
+impl base::Solver for Cooperative {
+    fn side(&self) -> bool {
+        self.side
+    }
+    fn solve(&mut self, field: &Field, ctx: &mut SolveContext) -> Vec<Act> {
+        let horizon = HORIZON.min(field.final_turn().saturating_sub(field.now_turn()) as usize);
+        self.roles.update(field);
+        let empty = Reservations::default();
+        // まずは単独で計画したときの得点が高い順に優先する
+        let mut order = (0..field.agent_count()).collect::<Vec<_>>();
+        let solo = order
+            .iter()
+            .map(|id| match field.agent(self.side, *id) {
+                Some(pos) => plan_agent(self.side, field, &empty, &self.roles, *id, pos, horizon).0,
+                None => 0.0,
+            })
+            .collect::<Vec<_>>();
+        order.sort_by(|a, b| solo[*b].partial_cmp(&solo[*a]).unwrap().then(a.cmp(b)));
+        let mut best = plan(self.side, field, &self.roles, &order, horizon);
+        for _ in 0..RESTARTS {
+            if ctx.remaining() < ctx.turn_sec() * 0.5 {
+                break;
+            }
+            order.shuffle(&mut ctx.rng);
+            let now = plan(self.side, field, &self.roles, &order, horizon);
+            if now.total() > best.total() {
+                best = now;
+            }
+        }
+
+        let mut acts = best
+            .paths
+            .iter()
+            .map(|path| path.first().map_or(Act::StayAct, |s| s.act.clone()))
+            .collect::<Vec<_>>();
+        for (id, pos) in placement::plan(self.side, field, &mut ctx.rng) {
+            acts[id] = Act::PutAct(pos);
+        }
+
+        let mut explanation = Explanation::new("cooperative", field, &acts);
+        explanation.add_stat("score", best.total());
+        for (id, path) in best.paths.iter().enumerate() {
+            if let (Some(start), Some(step)) = (field.agent(self.side, id), path.first()) {
+                let poses = Some(start)
+                    .into_iter()
+                    .chain(path.iter().map(|s| s.to))
+                    .collect();
+                explanation.add_candidate(id, step.act.clone(), best.scores[id], poses);
+            }
+            explanation.add_term(
+                id,
+                "priority",
+                best.order.iter().position(|x| *x == id).unwrap() as f64,
+            );
+            explanation.add_term(id, "solo", solo[id]);
+            if let Some(a) = self.roles.assignment(id) {
+                let name = format!("role_{:?}", a.role).to_lowercase();
+                explanation.add_term(id, &name, a.value);
+            }
+        }
+        self.explanation = Some(explanation);
+        acts
+    }
+    fn explain(&self) -> Option<&Explanation> {
+        self.explanation.as_ref()
+    }
+}
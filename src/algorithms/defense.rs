@@ -0,0 +1,85 @@
+use super::attack;
+use super::pathfind::{self, DistanceMap};
+use crate::field;
+
+use field::{Field, Point, PointUsize, State};
+use std::collections::HashMap;
+
+// 相手に除去されうる自分の壁
+#[derive(Clone, Debug)]
+pub struct Threat {
+    pub wall: Point,
+    // 除去されたときに失う壁の点と, 囲いの外とつながる陣地点の合計
+    pub stake: i16,
+    // (相手のエージェントの id, 除去できるまでのターン数)
+    pub attackers: Vec<(usize, usize)>,
+    // (自分のエージェントの id, 壁の上に立つまでのターン数).
+    // 相手より遅いエージェントは除去された後に壁を置き直す役になる
+    pub defenders: Vec<(usize, usize)>,
+}
+
+impl Threat {
+    pub fn turns(&self) -> usize {
+        self.attackers.iter().map(|x| x.1).min().unwrap_or(0)
+    }
+    // 壁の上に立っているか, 除去と同じターンに移動して打ち消せるなら守れる
+    pub fn guarded(&self) -> bool {
+        self.defenders.iter().any(|x| x.1 <= self.turns())
+    }
+}
+
+// k ターン以内に相手が除去できる自分の壁を, 失う点の大きい順に列挙する
+pub fn detect(side: bool, field: &Field, k: usize) -> Vec<Threat> {
+    // 途中の壁の除去も数えた各エージェントからの最短ターン数
+    let maps = |side: bool| {
+        (0..field.agent_count())
+            .filter_map(|id| {
+                let pos = field.agent(side, id)?;
+                Some((id, pathfind::distance_map(side, field, &[pos], k + 1)))
+            })
+            .collect::<Vec<(usize, DistanceMap)>>()
+    };
+    let opponents = maps(!side);
+    let agents = maps(side);
+    if opponents.is_empty() {
+        return Vec::new();
+    }
+    let exposed = attack::cuts(side, field)
+        .into_iter()
+        .map(|b| (b.wall, b.value))
+        .collect::<HashMap<_, _>>();
+
+    let mut res = Vec::new();
+    for x in 0..field.width() {
+        for y in 0..field.height() {
+            let wall = PointUsize::new(x, y).normal();
+            let tile = field.tile(wall.usize());
+            if tile.state() != State::Wall(side) {
+                continue;
+            }
+            // 相手から見ると自分の壁なので, 隣に着いてから除去し終えるまで
+            let attackers = opponents
+                .iter()
+                .filter_map(|(id, map)| Some((*id, map.removal(wall)?)))
+                .filter(|x| x.1 <= k)
+                .collect::<Vec<_>>();
+            let stake = tile.point().max(0) as i16 + exposed.get(&wall).unwrap_or(&0);
+            if attackers.is_empty() || stake <= 0 {
+                continue;
+            }
+            let mut defenders = agents
+                .iter()
+                .filter_map(|(id, map)| Some((*id, map.get(wall)?)))
+                .collect::<Vec<_>>();
+            defenders.sort_by_key(|x| (x.1, x.0));
+            res.push(Threat {
+                wall,
+                stake,
+                attackers,
+                defenders,
+            });
+        }
+    }
+    res.sort_by(|a, b| b.stake.cmp(&a.stake).then(a.wall.cmp(&b.wall)));
+    res
+}
@@ -0,0 +1,221 @@
+use super::pathfind::dist;
+use crate::field;
+
+use field::{Field, Point, PointUsize, State};
+use std::collections::HashSet;
+
+// 囲む領域 (内側) の縦横の最大サイズ
+const MAX_SIZE: usize = 5;
+// 1 つの囲いに割り当てるエージェントの最大数
+const MAX_AGENTS: usize = 2;
+// 見つかった中で最も良い効率のこの割合に届かない候補は詳しく調べない
+const PRUNE_RATE: f64 = 0.5;
+
+// 長方形の壁のループで囲む計画
+#[derive(Clone, Debug)]
+pub struct Enclosure {
+    // 壁にする必要のあるマスのうち, まだ自陣の壁でないもの (ループ順)
+    pub missing: Vec<Point>,
+    pub interior: Vec<Point>,
+    // 完成したときに増える得点の見積もり (内側の領域点 + 新しく置く壁の点)
+    pub value: i16,
+    // 必要な行動回数 (相手の壁は除去と移動で 2)
+    pub cost: usize,
+    // 割り当てたエージェントで完成するまでのターン数の見積もり
+    pub turns: usize,
+    // 相手の方が先に届く missing マスの割合
+    pub contest: f64,
+    // (エージェントの id, 順に壁にするマス)
+    pub assignment: Vec<(usize, Vec<Point>)>,
+}
+
+impl Enclosure {
+    pub fn efficiency(&self) -> f64 {
+        self.value as f64 * (1.0 - self.contest) / (self.turns + 1) as f64
+    }
+}
+
+fn tile_cost(side: bool, field: &Field, pos: Point) -> usize {
+    match field.tile(pos.usize()).state() {
+        State::Wall(s) if s == side => 0,
+        State::Wall(_) => 2,
+        _ => 1,
+    }
+}
+
+// (x, y) を左上とする, 内側が w * h の長方形のループを順に並べる
+fn ring(x: usize, y: usize, w: usize, h: usize) -> Vec<Point> {
+    let mut res = Vec::new();
+    let p = |x: usize, y: usize| PointUsize::new(x, y).normal();
+    for i in 0..w + 1 {
+        res.push(p(x + i, y));
+    }
+    for j in 0..h + 1 {
+        res.push(p(x + w + 1, y + j));
+    }
+    for i in 0..w + 1 {
+        res.push(p(x + w + 1 - i, y + h + 1));
+    }
+    for j in 0..h + 1 {
+        res.push(p(x, y + h + 1 - j));
+    }
+    res
+}
+
+fn evaluate(
+    side: bool,
+    field: &Field,
+    (x, y, w, h): (usize, usize, usize, usize),
+    agents: &[(usize, Point)],
+    opponents: &[Point],
+    min_efficiency: f64,
+) -> Option<Enclosure> {
+    let mut value = 0;
+    for i in 0..w {
+        for j in 0..h {
+            let tile = field.tile(PointUsize::new(x + 1 + i, y + 1 + j));
+            value += match tile.state() {
+                State::Wall(_) => 0,
+                State::Position(s) if s == side => 0,
+                _ => tile.point().abs() as i16,
+            };
+        }
+    }
+    let missing = ring(x, y, w, h)
+        .into_iter()
+        .filter(|pos| tile_cost(side, field, *pos) > 0)
+        .collect::<Vec<_>>();
+    if missing.is_empty() || agents.is_empty() {
+        return None;
+    }
+    value += missing.iter().fold(0, |b, pos| {
+        let point = field.tile(pos.usize()).point() as i16;
+        b + point * tile_cost(side, field, *pos) as i16
+    });
+    let cost = missing
+        .iter()
+        .fold(0, |b, pos| b + tile_cost(side, field, *pos));
+    // 全員で分担しても cost / MAX_AGENTS ターンと一番近いエージェントが届くまではかかるので,
+    // 明らかに効率の悪いものは先に捨てる
+    let reach = agents
+        .iter()
+        .map(|(_, pos)| missing.iter().map(|m| dist(*pos, *m)).min().unwrap())
+        .min()
+        .unwrap();
+    let least = cost.div_ceil(MAX_AGENTS).max(reach.max(1));
+    let bound = value as f64 / (least + 1) as f64;
+    if value <= 0 || bound < min_efficiency {
+        return None;
+    }
+
+    // ループ順に近いエージェントから連続する区間を割り当てる
+    let mut near = agents
+        .iter()
+        .map(|(id, pos)| {
+            (
+                missing.iter().map(|m| dist(*pos, *m)).min().unwrap(),
+                *id,
+                *pos,
+            )
+        })
+        .collect::<Vec<_>>();
+    near.sort();
+    let k = MAX_AGENTS.min(near.len()).min(missing.len());
+    let chunk = missing.len().div_ceil(k);
+    let mut used = Vec::new();
+    let mut assignment = Vec::new();
+    let mut turns = 0;
+    let mut contested = 0;
+    for part in missing.chunks(chunk) {
+        let (_, id, pos) = *near
+            .iter()
+            .filter(|x| !used.contains(&x.1))
+            .min_by_key(|x| (dist(x.2, part[0]), x.1))
+            .unwrap();
+        used.push(id);
+        let mut now = pos;
+        let mut t = 0;
+        for target in part {
+            t += dist(now, *target).max(1) - 1 + tile_cost(side, field, *target);
+            now = *target;
+            if opponents.iter().any(|o| dist(*o, *target) <= t) {
+                contested += 1;
+            }
+        }
+        turns = turns.max(t);
+        assignment.push((id, part.to_vec()));
+    }
+    let interior = (0..w)
+        .flat_map(|i| (0..h).map(move |j| PointUsize::new(x + 1 + i, y + 1 + j).normal()))
+        .collect();
+    Some(Enclosure {
+        contest: contested as f64 / missing.len() as f64,
+        missing,
+        interior,
+        value,
+        cost,
+        turns,
+        assignment,
+    })
+}
+
+// 残りターン内に完成できる囲いを効率の良い順に列挙する (効率の悪いものは途中で捨てる)
+pub fn find(side: bool, field: &Field) -> Vec<Enclosure> {
+    let agents = (0..field.agent_count())
+        .filter_map(|id| field.agent(side, id).map(|pos| (id, pos)))
+        .collect::<Vec<_>>();
+    let opponents = (0..field.agent_count())
+        .filter_map(|id| field.agent(!side, id))
+        .collect::<Vec<_>>();
+    let remain = field.final_turn().saturating_sub(field.now_turn()) as usize;
+    let mut res: Vec<Enclosure> = Vec::new();
+    let mut best = 0.0;
+    for w in 1..=MAX_SIZE {
+        for h in 1..=MAX_SIZE {
+            if field.width() < w + 2 || field.height() < h + 2 {
+                continue;
+            }
+            for x in 0..field.width() - w - 1 {
+                for y in 0..field.height() - h - 1 {
+                    let rect = (x, y, w, h);
+                    let min_efficiency = best * PRUNE_RATE;
+                    if let Some(e) =
+                        evaluate(side, field, rect, &agents, &opponents, min_efficiency)
+                    {
+                        if e.turns <= remain {
+                            best = e.efficiency().max(best);
+                            res.push(e);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    res.sort_by(|a, b| b.efficiency().partial_cmp(&a.efficiency()).unwrap());
+    res
+}
+
+// マスもエージェントも重ならないように上位の囲いを最大 count 個選ぶ
+pub fn propose(side: bool, field: &Field, count: usize) -> Vec<Enclosure> {
+    let mut tiles = HashSet::new();
+    let mut agents = HashSet::new();
+    let mut res = Vec::new();
+    for e in find(side, field) {
+        if res.len() >= count {
+            break;
+        }
+        if e.efficiency() <= 0.0
+            || e.missing
+                .iter()
+                .chain(&e.interior)
+                .any(|p| tiles.contains(p))
+            || e.assignment.iter().any(|(id, _)| agents.contains(id))
+        {
+            continue;
+        }
+        tiles.extend(e.missing.iter().chain(&e.interior).cloned());
+        agents.extend(e.assignment.iter().map(|(id, _)| *id));
+        res.push(e);
+    }
+    res
+}
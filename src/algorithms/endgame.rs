@@ -0,0 +1,207 @@
+use super::base;
+use super::context::SolveContext;
+use super::equilibrium;
+use super::explain::Explanation;
+use crate::field;
+use crate::simulator;
+
+use base::{diff, step};
+use field::{Field, Point};
+use simulator::Act;
+use std::collections::{HashMap, HashSet};
+
+// 残りターンがこれ以下なら終盤探索に切り替える
+pub const ENDGAME_TURNS: u8 = 2;
+// 各エージェントで考える手の数と, それを組み合わせた全体の手の数
+const AGENT_CAND: usize = 4;
+const OWN_CAND: usize = 12;
+const OPP_CAND: usize = 6;
+const EQ_ITER: usize = 1000;
+// 反復回数で持ち時間を数えるときの 1 局面の重さ (SolveContext::spend)
+const NODE_STEPS: usize = 200;
+
+// 残りターンの両チームの手を列挙し, 最終得点差の均衡 (根以外は maximin) を取る
+pub struct Endgame {
+    side: bool,
+    explanation: Option<Explanation>,
+}
+
+impl Endgame {
+    pub fn new(side: bool) -> Endgame {
+        Endgame {
+            side,
+            explanation: None,
+        }
+    }
+}
+
+pub fn applicable(field: &Field) -> bool {
+    field.final_turn().saturating_sub(field.now_turn()) <= ENDGAME_TURNS
+}
+
+impl base::Solver for Endgame {
+    fn side(&self) -> bool {
+        self.side
+    }
+    fn solve(&mut self, field: &Field, ctx: &mut SolveContext) -> Vec<Act> {
+        let depth = field
+            .final_turn()
+            .saturating_sub(field.now_turn())
+            .min(ENDGAME_TURNS);
+        if depth == 0 {
+            return vec![Act::StayAct; field.agent_count()];
+        }
+        // 根では得点差の行列を作り, 同時手番のゲームとして均衡を求める
+        let own = joint_candidates(self.side, field, OWN_CAND);
+        let opp = joint_candidates(!self.side, field, OPP_CAND);
+        // 浅い方から読み, 時間内に読み切れた最も深い行列を使う
+        let mut matrix = self.matrix(field, &own, &opp, 1, ctx);
+        let mut searched = 1;
+        for d in 2..=depth {
+            let deeper = self.matrix(field, &own, &opp, d, ctx);
+            if ctx.timed_out() {
+                break;
+            }
+            matrix = deeper;
+            searched = d;
+        }
+        let prob = equilibrium::solve_matrix(&matrix, EQ_ITER);
+        let best = (0..own.len())
+            .max_by(|a, b| prob[*a].partial_cmp(&prob[*b]).unwrap().then(b.cmp(a)))
+            .expect("no candidate");
+
+        // 候補の値は, その手を含む手の組の均衡での確率の合計
+        let mut explanation = Explanation::new("endgame", field, &own[best]);
+        for id in 0..field.agent_count() {
+            let mut marginal = HashMap::new();
+            for (acts, p) in own.iter().zip(&prob) {
+                *marginal.entry(acts[id].clone()).or_insert(0.0) += p;
+            }
+            for (act, p) in marginal {
+                explanation.add_candidate(id, act, p, Vec::new());
+            }
+        }
+        explanation.finish();
+        explanation.add_stat("depth", searched as f64);
+        explanation.add_stat("probability", prob[best]);
+        self.explanation = Some(explanation);
+        own[best].clone()
+    }
+    fn explain(&self) -> Option<&Explanation> {
+        self.explanation.as_ref()
+    }
+}
+
+impl Endgame {
+    fn joint(&self, acts: &[Act], opp_acts: &[Act]) -> Vec<Vec<Act>> {
+        let mut joint = vec![Vec::new(); 2];
+        joint[self.side as usize] = acts.to_vec();
+        joint[!self.side as usize] = opp_acts.to_vec();
+        joint
+    }
+    fn matrix(
+        &self,
+        field: &Field,
+        own: &[Vec<Act>],
+        opp: &[Vec<Act>],
+        depth: u8,
+        ctx: &SolveContext,
+    ) -> Vec<Vec<i16>> {
+        own.iter()
+            .map(|acts| {
+                opp.iter()
+                    .map(|opp_acts| {
+                        let next = step(field, &self.joint(acts, opp_acts));
+                        self.maximin(&next, depth - 1, i16::min_value(), i16::max_value(), ctx)
+                            .0
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+    // alpha 以下にしかならない手と, beta 以上になって相手が選ばない局面は打ち切る
+    fn maximin(
+        &self,
+        field: &Field,
+        depth: u8,
+        alpha: i16,
+        beta: i16,
+        ctx: &SolveContext,
+    ) -> (i16, Option<Vec<Act>>) {
+        if depth == 0 || field.now_turn() >= field.final_turn() {
+            return (diff(self.side, field), None);
+        }
+        let own = joint_candidates(self.side, field, OWN_CAND);
+        let opp = joint_candidates(!self.side, field, OPP_CAND);
+        let mut best = (i16::min_value(), None);
+        for acts in own {
+            let mut worst = i16::max_value();
+            for opp_acts in &opp {
+                let next = step(field, &self.joint(&acts, opp_acts));
+                // 時間切れなら残りは読まない (呼び出し側でこの結果は捨てる)
+                let value = if ctx.spend(NODE_STEPS) {
+                    diff(self.side, &next)
+                } else {
+                    self.maximin(&next, depth - 1, alpha.max(best.0), worst, ctx)
+                        .0
+                };
+                worst = worst.min(value);
+                if worst <= alpha.max(best.0) && best.1.is_some() {
+                    break;
+                }
+            }
+            if best.1.is_none() || worst > best.0 {
+                best = (worst, Some(acts));
+            }
+            if best.0 >= beta {
+                break;
+            }
+        }
+        best
+    }
+}
+
+// 各エージェントの上位の手を組み合わせ, 行き先が重ならないものを合計得点の高い順にビームで残す
+pub fn joint_candidates(side: bool, field: &Field, width: usize) -> Vec<Vec<Act>> {
+    let mut beam: Vec<(i32, Vec<Act>, HashSet<Point>)> = vec![(0, Vec::new(), HashSet::new())];
+    for id in 0..field.agent_count() {
+        let mut cand = base::make_acts(side, id, field)
+            .into_iter()
+            .filter_map(|act| match act {
+                Act::StayAct => Some((0, act)),
+                _ => base::point(side, act.clone(), field).map(|p| (p as i32, act)),
+            })
+            .collect::<Vec<_>>();
+        cand.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        cand.truncate(AGENT_CAND);
+        if cand.iter().all(|x| x.1 != Act::StayAct) {
+            cand.push((0, Act::StayAct));
+        }
+
+        let mut next = Vec::new();
+        for (score, acts, used) in &beam {
+            for (point, act) in &cand {
+                let target = match act {
+                    Act::PutAct(p) | Act::MoveAct(p) | Act::RemoveAct(p) => Some(*p),
+                    Act::StayAct => None,
+                };
+                if let Some(p) = target {
+                    if used.contains(&p) {
+                        continue;
+                    }
+                }
+                let mut acts = acts.to_vec();
+                acts.push(act.clone());
+                let mut used = used.clone();
+                if let Some(p) = target {
+                    used.insert(p);
+                }
+                next.push((score + point, acts, used));
+            }
+        }
+        next.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        next.truncate(width);
+        beam = next;
+    }
+    beam.into_iter().map(|x| x.1).collect()
+}
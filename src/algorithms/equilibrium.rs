@@ -0,0 +1,281 @@
+use super::context::SolveContext;
+use crate::field;
+use crate::simulator;
+
+use field::Field;
+use rand::rngs::StdRng;
+use rand::Rng;
+use simulator::Act;
+use std::collections::HashMap;
+
+// 反復回数で持ち時間を数えるときの 1 反復の重さ (SolveContext::spend)
+const ITER_STEPS: usize = 2000;
+
+// 各エージェントを 1 人のプレイヤーとみなした零和ゲームを, 手の組をサンプリングする regret matching+ で解く.
+// 利得は実際に適用される手の評価値の合計の, 自チームと相手チームの差
+pub struct Equilibrium {
+    // [side][id][候補]
+    acts: Vec<Vec<Vec<Act>>>,
+    scores: Vec<Vec<Vec<f64>>>,
+    regret: Vec<Vec<Vec<f64>>>,
+    // 重み付きの戦略の累積 (平均戦略)
+    average: Vec<Vec<Vec<f64>>>,
+    iterations: usize,
+}
+
+fn normalize(v: &[f64]) -> Vec<f64> {
+    let total = v.iter().sum::<f64>();
+    if total <= 0.0 {
+        vec![1.0 / v.len() as f64; v.len()]
+    } else {
+        v.iter().map(|x| x / total).collect()
+    }
+}
+
+fn sample_index(prob: &[f64], rng: &mut StdRng) -> usize {
+    let per = rng.gen::<f64>();
+    let mut sum = 0.0;
+    for (i, p) in prob.iter().enumerate() {
+        sum += p;
+        if per < sum {
+            return i;
+        }
+    }
+    prob.len() - 1
+}
+
+// 行側が最大化する零和の行列ゲームを regret matching+ で解き, 行側の平均戦略を返す
+pub fn solve_matrix(matrix: &[Vec<i16>], num_iter: usize) -> Vec<f64> {
+    let n = matrix.len();
+    let m = matrix[0].len();
+    let mut regret = [vec![0.0; n], vec![0.0; m]];
+    let mut sum = vec![0.0; n];
+    for t in 0..num_iter {
+        let row = normalize(&regret[0]);
+        let col = normalize(&regret[1]);
+        let row_values = (0..n)
+            .map(|i| (0..m).fold(0.0, |b, j| b + col[j] * matrix[i][j] as f64))
+            .collect::<Vec<_>>();
+        let col_values = (0..m)
+            .map(|j| (0..n).fold(0.0, |b, i| b - row[i] * matrix[i][j] as f64))
+            .collect::<Vec<_>>();
+        let row_value = (0..n).fold(0.0, |b, i| b + row[i] * row_values[i]);
+        let col_value = (0..m).fold(0.0, |b, j| b + col[j] * col_values[j]);
+        for i in 0..n {
+            regret[0][i] = (regret[0][i] + row_values[i] - row_value).max(0.0);
+            // 後の反復ほど重くする (linear averaging)
+            sum[i] += (t + 1) as f64 * row[i];
+        }
+        for j in 0..m {
+            regret[1][j] = (regret[1][j] + col_values[j] - col_value).max(0.0);
+        }
+    }
+    normalize(&sum)
+}
+
+impl Equilibrium {
+    // act_scores[side][id] は各エージェントの手の評価値. 評価できる手がなければ停止だけを考える
+    pub fn new(act_scores: &[Vec<HashMap<Act, f64>>]) -> Equilibrium {
+        let mut acts = vec![Vec::new(); 2];
+        let mut scores = vec![Vec::new(); 2];
+        for side in 0..2 {
+            for hm in &act_scores[side] {
+                let mut items = hm.iter().map(|(a, s)| (a.clone(), *s)).collect::<Vec<_>>();
+                items.sort_by(|a, b| a.0.cmp(&b.0));
+                if items.is_empty() {
+                    items.push((Act::StayAct, 0.0));
+                }
+                acts[side].push(items.iter().map(|x| x.0.clone()).collect::<Vec<_>>());
+                scores[side].push(items.iter().map(|x| x.1).collect::<Vec<_>>());
+            }
+        }
+        let zeros = scores
+            .iter()
+            .map(|v| v.iter().map(|s| vec![0.0; s.len()]).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        Equilibrium {
+            acts,
+            scores,
+            regret: zeros.clone(),
+            average: zeros,
+            iterations: 0,
+        }
+    }
+    // side の初期戦略を prob にし, 平均戦略にも weight 反復分の重みで入れる (相手のモデルなど).
+    // 分布は固定しないので, 反復を重ねれば両チームとも均衡に近づく
+    pub fn prior(&mut self, side: bool, prob: &[HashMap<Act, f64>], weight: f64) {
+        let side = side as usize;
+        for (id, hm) in prob.iter().enumerate() {
+            let p = self.acts[side][id]
+                .iter()
+                .map(|a| *hm.get(a).unwrap_or(&0.0))
+                .collect::<Vec<_>>();
+            if p.iter().sum::<f64>() <= 0.0 {
+                continue;
+            }
+            let p = normalize(&p);
+            self.regret[side][id] = p.clone();
+            self.average[side][id] = p.iter().map(|x| x * weight).collect();
+        }
+    }
+    pub fn iterations(&self) -> usize {
+        self.iterations
+    }
+    fn current(&self, side: usize, id: usize) -> Vec<f64> {
+        normalize(&self.regret[side][id])
+    }
+    fn sample_profile(&self, average: bool, rng: &mut StdRng) -> Vec<Vec<usize>> {
+        (0..2)
+            .map(|side| {
+                (0..self.acts[side].len())
+                    .map(|id| {
+                        let prob = if average {
+                            normalize(&self.average[side][id])
+                        } else {
+                            self.current(side, id)
+                        };
+                        sample_index(&prob, rng)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+    // 手の組を適用したときの side から見た利得
+    fn utility(&self, side: usize, profile: &[Vec<usize>], field: &Field) -> f64 {
+        let acts = (0..2)
+            .map(|s| {
+                profile[s]
+                    .iter()
+                    .enumerate()
+                    .map(|(id, i)| self.acts[s][id][*i].clone())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        // 無効になって停止に置き換わった手は 0 点
+        simulator::act_list(&acts, field)
+            .into_iter()
+            .fold(0.0, |b, (s, id, act)| {
+                let s = s as usize;
+                let score = match self.acts[s][id].iter().position(|a| *a == act) {
+                    Some(i) => self.scores[s][id][i],
+                    None => 0.0,
+                };
+                if s == side {
+                    b + score
+                } else {
+                    b - score
+                }
+            })
+    }
+    // 他のエージェントの手を固定して, (side, id) の各候補に変えたときの利得
+    fn counterfactual(
+        &self,
+        side: usize,
+        id: usize,
+        profile: &mut Vec<Vec<usize>>,
+        field: &Field,
+    ) -> Vec<f64> {
+        let now = profile[side][id];
+        let values = (0..self.acts[side][id].len())
+            .map(|i| {
+                profile[side][id] = i;
+                self.utility(side, profile, field)
+            })
+            .collect();
+        profile[side][id] = now;
+        values
+    }
+    pub fn run(&mut self, field: &Field, num_iter: usize, ctx: &mut SolveContext) {
+        for _ in 0..num_iter {
+            if ctx.spend(ITER_STEPS) {
+                break;
+            }
+            self.iterations += 1;
+            let mut profile = self.sample_profile(false, &mut ctx.rng);
+            for side in 0..2 {
+                for id in 0..self.acts[side].len() {
+                    let prob = self.current(side, id);
+                    let values = self.counterfactual(side, id, &mut profile, field);
+                    let value = values.iter().zip(&prob).fold(0.0, |b, (v, p)| b + v * p);
+                    for (i, v) in values.iter().enumerate() {
+                        let r = &mut self.regret[side][id][i];
+                        *r = (*r + v - value).max(0.0);
+                        // 後の反復ほど重くする (linear averaging)
+                        self.average[side][id][i] += self.iterations as f64 * prob[i];
+                    }
+                }
+            }
+        }
+    }
+    // 平均戦略
+    pub fn strategy(&self, side: bool) -> Vec<HashMap<Act, f64>> {
+        let side = side as usize;
+        self.acts[side]
+            .iter()
+            .zip(&self.average[side])
+            .map(|(acts, avg)| acts.iter().cloned().zip(normalize(avg)).collect())
+            .collect()
+    }
+    pub fn sample(&self, side: bool, rng: &mut StdRng) -> Vec<Act> {
+        let profile = self.sample_profile(true, rng);
+        let side = side as usize;
+        profile[side]
+            .iter()
+            .enumerate()
+            .map(|(id, i)| self.acts[side][id][*i].clone())
+            .collect()
+    }
+    // 平均戦略の手の組を samples 回引き, 各エージェントの各候補の期待利得を求める
+    fn expected(
+        &self,
+        side: usize,
+        field: &Field,
+        samples: usize,
+        rng: &mut StdRng,
+    ) -> Vec<Vec<f64>> {
+        let mut res = self.scores[side]
+            .iter()
+            .map(|s| vec![0.0; s.len()])
+            .collect::<Vec<_>>();
+        for _ in 0..samples {
+            let mut profile = self.sample_profile(true, rng);
+            for (id, res) in res.iter_mut().enumerate() {
+                let values = self.counterfactual(side, id, &mut profile, field);
+                for (i, v) in values.iter().enumerate() {
+                    res[i] += v / samples as f64;
+                }
+            }
+        }
+        res
+    }
+    // 平均戦略に対して各エージェントが 1 人だけ手を変えて得られる利得の合計 (両チーム分).
+    // 均衡に近いほど 0 に近づく
+    pub fn exploitability(&self, field: &Field, samples: usize, rng: &mut StdRng) -> f64 {
+        let mut res = 0.0;
+        for side in 0..2 {
+            let expected = self.expected(side, field, samples, rng);
+            for (id, values) in expected.iter().enumerate() {
+                let prob = normalize(&self.average[side][id]);
+                let value = values.iter().zip(&prob).fold(0.0, |b, (v, p)| b + v * p);
+                let best = values.iter().fold(f64::MIN, |b, v| b.max(*v));
+                res += best - value;
+            }
+        }
+        res
+    }
+    // 平均戦略の相手に対する各エージェントの手の期待利得 (手の割り当てにそのまま使える)
+    pub fn best_response(
+        &self,
+        side: bool,
+        field: &Field,
+        samples: usize,
+        rng: &mut StdRng,
+    ) -> Vec<HashMap<Act, f64>> {
+        let expected = self.expected(side as usize, field, samples, rng);
+        self.acts[side as usize]
+            .iter()
+            .zip(expected)
+            .map(|(acts, values)| acts.iter().cloned().zip(values).collect())
+            .collect()
+    }
+}
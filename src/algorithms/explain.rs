@@ -0,0 +1,124 @@
+use crate::field;
+use crate::simulator;
+
+use field::{Field, Point};
+use serde::{Deserialize, Serialize};
+use simulator::Act;
+use std::collections::HashMap;
+use std::fmt;
+
+// エージェントごとに残す候補の数
+const TOP_K: usize = 5;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Candidate {
+    pub act: Act,
+    pub score: f64,
+    // 先読みした経路 (先頭は現在地). 1 手だけで評価した候補は空
+    pub path: Vec<Point>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AgentExplanation {
+    pub id: usize,
+    pub chosen: Act,
+    // 評価値の高い順
+    pub candidates: Vec<Candidate>,
+    // 選んだ手の評価に加えた項 (名前, 値)
+    pub terms: Vec<(String, f64)>,
+}
+
+// ソルバーが最後の solve で手を選んだ理由
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Explanation {
+    pub solver: String,
+    pub turn: u8,
+    pub agents: Vec<AgentExplanation>,
+    // 手の組全体についての値 (評価値, exploitability など)
+    pub stats: Vec<(String, f64)>,
+}
+
+impl Explanation {
+    pub fn new(solver: &str, field: &Field, chosen: &[Act]) -> Explanation {
+        Explanation {
+            solver: solver.to_string(),
+            turn: field.now_turn(),
+            agents: chosen
+                .iter()
+                .enumerate()
+                .map(|(id, act)| AgentExplanation {
+                    id,
+                    chosen: act.clone(),
+                    candidates: Vec::new(),
+                    terms: Vec::new(),
+                })
+                .collect(),
+            stats: Vec::new(),
+        }
+    }
+    // 各エージェントの手の評価値から作る
+    pub fn from_scores(
+        solver: &str,
+        field: &Field,
+        scores: &[HashMap<Act, f64>],
+        chosen: &[Act],
+    ) -> Explanation {
+        let mut res = Explanation::new(solver, field, chosen);
+        for (id, hm) in scores.iter().enumerate() {
+            for (act, score) in hm {
+                res.add_candidate(id, act.clone(), *score, Vec::new());
+            }
+        }
+        res.finish();
+        res
+    }
+    pub fn add_candidate(&mut self, id: usize, act: Act, score: f64, path: Vec<Point>) {
+        self.agents[id]
+            .candidates
+            .push(Candidate { act, score, path });
+    }
+    pub fn add_term(&mut self, id: usize, name: &str, value: f64) {
+        self.agents[id].terms.push((name.to_string(), value));
+    }
+    pub fn add_stat(&mut self, name: &str, value: f64) {
+        self.stats.push((name.to_string(), value));
+    }
+    // 候補を評価値の高い順に並べて上位だけ残す
+    pub fn finish(&mut self) {
+        for agent in &mut self.agents {
+            agent.candidates.sort_by(|a, b| {
+                b.score
+                    .partial_cmp(&a.score)
+                    .unwrap()
+                    .then(a.act.cmp(&b.act))
+            });
+            agent.candidates.truncate(TOP_K);
+        }
+    }
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string(self).map_err(|e| e.to_string())
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}] turn {}", self.solver, self.turn)?;
+        for (name, value) in &self.stats {
+            write!(f, " {}={:.3}", name, value)?;
+        }
+        for agent in &self.agents {
+            write!(f, "\n  agent {}: {:?}", agent.id, agent.chosen)?;
+            for (name, value) in &agent.terms {
+                write!(f, " {}={:.3}", name, value)?;
+            }
+            for c in &agent.candidates {
+                write!(f, "\n    {:.3} {:?}", c.score, c.act)?;
+                if !c.path.is_empty() {
+                    let path = c.path.iter().map(|p| format!("({},{})", p.x, p.y));
+                    write!(f, " {}", path.collect::<Vec<_>>().join("-"))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
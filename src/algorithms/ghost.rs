@@ -0,0 +1,66 @@
+use super::base;
+use super::context::SolveContext;
+use super::explain::Explanation;
+use crate::field;
+use crate::record;
+use crate::simulator;
+
+use field::Field;
+use record::GameRecord;
+use simulator::Act;
+
+// 記録された相手の手をそのまま再生し, 盤面が記録とずれたら fallback に切り替える
+pub struct Ghost {
+    side: bool,
+    record: GameRecord,
+    fallback: Box<dyn base::Solver>,
+    diverged: bool,
+    // 記録を再生したターンの説明 (候補はない)
+    replayed: Option<Explanation>,
+}
+
+impl Ghost {
+    pub fn new(side: bool, record: GameRecord, fallback: Box<dyn base::Solver>) -> Ghost {
+        Ghost {
+            side,
+            record,
+            fallback,
+            diverged: false,
+            replayed: None,
+        }
+    }
+    pub fn diverged(&self) -> bool {
+        self.diverged
+    }
+}
+
+impl base::Solver for Ghost {
+    fn side(&self) -> bool {
+        self.side
+    }
+    fn solve(&mut self, field: &Field, ctx: &mut SolveContext) -> Vec<Act> {
+        match self.record.acts(self.side, field) {
+            Some(acts) if !self.diverged => {
+                self.replayed = Some(Explanation::new("ghost", field, &acts));
+                acts
+            }
+            _ => {
+                if !self.diverged {
+                    self.diverged = true;
+                    eprintln!("ghost diverged at turn {}", field.now_turn());
+                }
+                self.fallback.solve(field, ctx)
+            }
+        }
+    }
+    fn update(&mut self, field: &Field, resolved: &[Vec<Act>]) {
+        self.fallback.update(field, resolved);
+    }
+    fn explain(&self) -> Option<&Explanation> {
+        if self.diverged {
+            self.fallback.explain()
+        } else {
+            self.replayed.as_ref()
+        }
+    }
+}
@@ -1,32 +1,42 @@
 use super::base;
+use super::context::SolveContext;
+use super::explain::Explanation;
 use crate::field;
 use crate::simulator;
 
 use field::Field;
 use simulator::Act;
 
-pub struct GreedySelect<'a> {
-    field: &'a Field,
+pub struct GreedySelect {
     side: bool,
+    explanation: Option<Explanation>,
 }
 
-impl<'a> base::Solver<'a> for GreedySelect<'a> {
-    fn new(side: bool, field: &'a Field) -> GreedySelect<'a> {
-        GreedySelect { field, side }
-    }
-    fn field(&self) -> &Field {
-        self.field
+impl GreedySelect {
+    pub fn new(side: bool) -> GreedySelect {
+        GreedySelect {
+            side,
+            explanation: None,
+        }
     }
+}
+
+impl base::Solver for GreedySelect {
     fn side(&self) -> bool {
         self.side
     }
-    fn solve(&mut self) -> Vec<Act> {
-        base::solve(self)
+    fn solve(&mut self, field: &Field, _ctx: &mut SolveContext) -> Vec<Act> {
+        let (acts, explanation) = base::solve(self, field, "greedy_select");
+        self.explanation = Some(explanation);
+        acts
+    }
+    fn explain(&self) -> Option<&Explanation> {
+        self.explanation.as_ref()
     }
 }
 
-impl<'a> base::EachEvalSolver for GreedySelect<'a> {
-    fn eval(&self, _id: usize, act: Act) -> Option<f64> {
-        base::point(self.side, act, self.field).map(|x| x as f64)
+impl base::EachEvalSolver for GreedySelect {
+    fn eval(&self, field: &Field, _id: usize, act: Act) -> Option<f64> {
+        base::point(self.side, act, field).map(|x| x as f64)
     }
 }
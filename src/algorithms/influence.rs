@@ -0,0 +1,84 @@
+use super::pathfind;
+use crate::field;
+
+use field::{Field, PointUsize};
+
+// 距離 d のマスに実際に向かう確率 (遠いほど他のマスに向かいやすい)
+const REACH_DECAY: f64 = 0.6;
+
+// 各マスに各チームのエージェントが何ターン目までに届くかの分布と, そこから求めた支配・競合の確率
+pub struct Influence {
+    width: usize,
+    horizon: usize,
+    // [side][マス][t] t ターン目までにいずれかのエージェントが届いている確率
+    reach: Vec<Vec<Vec<f64>>>,
+}
+
+impl Influence {
+    // 配置済みのエージェントについて horizon ターン先まで求める
+    pub fn new(field: &Field, horizon: usize) -> Influence {
+        let size = field.width() * field.height();
+        let mut reach = vec![vec![vec![0.0; horizon + 1]; size]; 2];
+        for side in [false, true] {
+            // 届いていない確率の積
+            let mut miss = vec![vec![1.0; horizon + 1]; size];
+            for id in 0..field.agent_count() {
+                let pos = match field.agent(side, id) {
+                    Some(pos) => pos,
+                    None => continue,
+                };
+                let map = pathfind::distance_map(side, field, &[pos], horizon);
+                for (i, d) in map.distances().iter().enumerate() {
+                    if let Some(d) = d {
+                        let p = REACH_DECAY.powi(*d as i32);
+                        for m in &mut miss[i][*d..] {
+                            *m *= 1.0 - p;
+                        }
+                    }
+                }
+            }
+            for i in 0..size {
+                for t in 0..=horizon {
+                    reach[side as usize][i][t] = 1.0 - miss[i][t];
+                }
+            }
+        }
+        Influence {
+            width: field.width(),
+            horizon,
+            reach,
+        }
+    }
+    pub fn horizon(&self) -> usize {
+        self.horizon
+    }
+    fn index(&self, pos: PointUsize) -> usize {
+        pos.y * self.width + pos.x
+    }
+    // t ターン目までに side のいずれかのエージェントが届いている確率
+    pub fn reach(&self, side: bool, pos: PointUsize, t: usize) -> f64 {
+        self.reach[side as usize][self.index(pos)][t.min(self.horizon)]
+    }
+    // side が最初に届くのがちょうど t ターン目である確率 (0..=horizon)
+    pub fn arrival(&self, side: bool, pos: PointUsize) -> Vec<f64> {
+        let r = &self.reach[side as usize][self.index(pos)];
+        (0..=self.horizon)
+            .map(|t| if t == 0 { r[0] } else { r[t] - r[t - 1] })
+            .collect()
+    }
+    // side が相手より先に (相手が届かないうちに) 届く確率
+    pub fn control(&self, side: bool, pos: PointUsize) -> f64 {
+        let theirs = &self.reach[!side as usize][self.index(pos)];
+        self.arrival(side, pos)
+            .iter()
+            .zip(theirs)
+            .fold(0.0, |b, (p, r)| b + p * (1.0 - r))
+    }
+    // 両チームが同じターンに初めて届いて取り合いになる確率
+    pub fn contest(&self, pos: PointUsize) -> f64 {
+        self.arrival(false, pos)
+            .iter()
+            .zip(self.arrival(true, pos))
+            .fold(0.0, |b, (p, q)| b + p * q)
+    }
+}
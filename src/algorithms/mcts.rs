@@ -0,0 +1,282 @@
+use super::base;
+use super::context::SolveContext;
+use super::explain::Explanation;
+use crate::field;
+use crate::simulator;
+
+use base::{diff, step};
+use field::Field;
+use rand::rngs::StdRng;
+use rand::Rng;
+use simulator::Act;
+use std::collections::HashMap;
+
+// 木を展開する深さ (ターン数). その先はプレイアウトで評価する
+const MAX_DEPTH: usize = 3;
+const ROLLOUT_DEPTH: usize = 4;
+const PUT_CAND: usize = 8;
+const UCB_C: f64 = 0.5;
+// 試行回数が少ないうちはマスの得点を事前知識として UCB に足す
+const PRIOR_WEIGHT: f64 = 1.0;
+const ROLLOUT_EPS: f64 = 0.1;
+// 得点差をエージェント 1 人あたりこの値で割って [-1, 1] に収める
+const SCORE_SCALE: f64 = 10.0;
+const TIME_RATE: f64 = 0.9;
+// 反復回数で持ち時間を数えるときの 1 回のプレイアウトの重さ (SolveContext::spend)
+const PLAYOUT_STEPS: usize = 1000;
+
+// 同時手番の MCTS (decoupled UCT). 各エージェントが独立に UCB で手を選ぶ
+pub struct Mcts {
+    side: bool,
+    explanation: Option<Explanation>,
+}
+
+struct Stat {
+    act: Act,
+    prior: f64,
+    visit: f64,
+    value: f64,
+}
+
+struct Node {
+    field: Field,
+    visit: f64,
+    // [side][id][候補]
+    stats: Vec<Vec<Vec<Stat>>>,
+    children: HashMap<Vec<Vec<Act>>, usize>,
+}
+
+impl Mcts {
+    pub fn new(side: bool) -> Mcts {
+        Mcts {
+            side,
+            explanation: None,
+        }
+    }
+}
+
+impl base::Solver for Mcts {
+    fn side(&self) -> bool {
+        self.side
+    }
+    fn solve(&mut self, field: &Field, ctx: &mut SolveContext) -> Vec<Act> {
+        let sec = ctx.remaining() * TIME_RATE;
+        let mut ctx = ctx.split(sec);
+        let base_diff = diff(self.side, field);
+        let mut tree = vec![Node::new(field.clone())];
+
+        while !ctx.spend(PLAYOUT_STEPS) {
+            // 選択
+            let mut path = Vec::new();
+            let mut idx = 0;
+            let mut expanded = false;
+            for _ in 0..MAX_DEPTH {
+                if is_end(&tree[idx].field) {
+                    break;
+                }
+                let sel = tree[idx].select(&mut ctx.rng);
+                let acts = tree[idx].acts(&sel);
+                path.push((idx, sel));
+                idx = match tree[idx].children.get(&acts) {
+                    Some(child) => *child,
+                    None => {
+                        // 展開
+                        let child = tree.len();
+                        let next = step(&tree[idx].field, &acts);
+                        tree.push(Node::new(next));
+                        tree[idx].children.insert(acts, child);
+                        expanded = true;
+                        child
+                    }
+                };
+                if expanded {
+                    break;
+                }
+            }
+
+            // プレイアウト
+            let end = rollout(&tree[idx].field, ROLLOUT_DEPTH, &mut ctx.rng);
+            let value = ((diff(self.side, &end) - base_diff) as f64
+                / (SCORE_SCALE * field.agent_count().max(1) as f64))
+                .clamp(-1.0, 1.0);
+
+            // 逆伝播
+            tree[idx].visit += 1.0;
+            for (idx, sel) in path {
+                let node = &mut tree[idx];
+                node.visit += 1.0;
+                for (side, sel) in sel.iter().enumerate() {
+                    let v = if (side == 1) == self.side {
+                        value
+                    } else {
+                        -value
+                    };
+                    for (id, i) in sel.iter().enumerate() {
+                        let stat = &mut node.stats[side][id][*i];
+                        stat.visit += 1.0;
+                        stat.value += v;
+                    }
+                }
+            }
+        }
+
+        // 根で最も多く試された手を選ぶ. 並んだら (一度も試せなかった場合も) 事前知識の高い方
+        let root = &tree[0].stats[self.side as usize];
+        let acts = root
+            .iter()
+            .map(|v| {
+                v.iter()
+                    .max_by(|a, b| {
+                        a.visit
+                            .partial_cmp(&b.visit)
+                            .unwrap()
+                            .then(a.prior.partial_cmp(&b.prior).unwrap())
+                    })
+                    .map(|x| x.act.clone())
+                    .unwrap_or(Act::StayAct)
+            })
+            .collect::<Vec<_>>();
+
+        // 候補の値は試した手の平均の評価
+        let mut explanation = Explanation::new("mcts", field, &acts);
+        for (id, v) in root.iter().enumerate() {
+            for stat in v.iter().filter(|x| x.visit > 0.0) {
+                explanation.add_candidate(
+                    id,
+                    stat.act.clone(),
+                    stat.value / stat.visit,
+                    Vec::new(),
+                );
+                if stat.act == acts[id] {
+                    explanation.add_term(id, "visit", stat.visit);
+                }
+            }
+        }
+        explanation.finish();
+        explanation.add_stat("playouts", tree[0].visit);
+        explanation.add_stat("nodes", tree.len() as f64);
+        self.explanation = Some(explanation);
+        acts
+    }
+    fn explain(&self) -> Option<&Explanation> {
+        self.explanation.as_ref()
+    }
+}
+
+impl Node {
+    fn new(field: Field) -> Node {
+        let stats = [false, true]
+            .iter()
+            .map(|side| {
+                (0..field.agent_count())
+                    .map(|id| {
+                        candidates(*side, id, &field)
+                            .into_iter()
+                            .map(|act| Stat {
+                                prior: base::point(*side, act.clone(), &field).unwrap_or(0) as f64
+                                    / 16.0,
+                                act,
+                                visit: 0.0,
+                                value: 0.0,
+                            })
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect();
+        Node {
+            field,
+            visit: 0.0,
+            stats,
+            children: HashMap::new(),
+        }
+    }
+    fn select(&self, rng: &mut StdRng) -> Vec<Vec<usize>> {
+        self.stats
+            .iter()
+            .map(|side| {
+                side.iter()
+                    .map(|stats| {
+                        let mut ucb = |s: &Stat| {
+                            if s.visit == 0.0 {
+                                return 1e9 + s.prior + rng.gen::<f64>() * 1e-3;
+                            }
+                            s.value / s.visit
+                                + UCB_C * (self.visit.ln() / s.visit).sqrt()
+                                + PRIOR_WEIGHT * s.prior / (s.visit + 1.0)
+                        };
+                        let values = stats.iter().map(|s| ucb(s)).collect::<Vec<_>>();
+                        (0..stats.len())
+                            .max_by(|a, b| values[*a].partial_cmp(&values[*b]).unwrap())
+                            .unwrap()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+    fn acts(&self, sel: &[Vec<usize>]) -> Vec<Vec<Act>> {
+        sel.iter()
+            .zip(&self.stats)
+            .map(|(v, stats)| {
+                v.iter()
+                    .zip(stats)
+                    .map(|(i, s)| s[*i].act.clone())
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+// 自陣の壁の除去などの明らかに悪い手を除き, 配置は得点の高いマスだけに絞る
+fn candidates(side: bool, id: usize, field: &Field) -> Vec<Act> {
+    let acts = base::make_acts(side, id, field);
+    if field.agent(side, id).is_none() {
+        let mut puts = acts
+            .into_iter()
+            .filter_map(|act| base::point(side, act.clone(), field).map(|p| (p, act)))
+            .collect::<Vec<_>>();
+        puts.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        let mut cand = vec![Act::StayAct];
+        cand.extend(puts.into_iter().take(PUT_CAND).map(|x| x.1));
+        return cand;
+    }
+    acts.into_iter()
+        .filter(|act| {
+            *act == Act::StayAct || base::point(side, act.clone(), field).unwrap_or(-1) >= 0
+        })
+        .collect()
+}
+
+// プレイアウトは各エージェントが ε-greedy に手を選ぶ
+fn rollout_act(side: bool, id: usize, field: &Field, rng: &mut StdRng) -> Act {
+    let cand = candidates(side, id, field);
+    if rng.gen::<f64>() < ROLLOUT_EPS {
+        return cand[rng.gen_range(0, cand.len())].clone();
+    }
+    cand.into_iter()
+        .max_by_key(|act| base::point(side, act.clone(), field).unwrap_or(0))
+        .unwrap_or(Act::StayAct)
+}
+
+fn rollout(field: &Field, depth: usize, rng: &mut StdRng) -> Field {
+    let mut field = field.clone();
+    for _ in 0..depth {
+        if is_end(&field) {
+            break;
+        }
+        let acts = [false, true]
+            .iter()
+            .map(|side| {
+                (0..field.agent_count())
+                    .map(|id| rollout_act(*side, id, &field, rng))
+                    .collect()
+            })
+            .collect::<Vec<Vec<Act>>>();
+        field = step(&field, &acts);
+    }
+    field
+}
+
+fn is_end(field: &Field) -> bool {
+    field.now_turn() >= field.final_turn()
+}
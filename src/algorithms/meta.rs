@@ -0,0 +1,284 @@
+use super::assignment;
+use super::base;
+use super::context::SolveContext;
+use super::endgame;
+use super::explain::Explanation;
+use super::registry;
+use crate::field;
+use crate::simulator;
+
+use field::{Field, PointUsize};
+use serde::{Deserialize, Serialize};
+use simulator::Act;
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::File;
+
+const FEATURES: [&str; 10] = [
+    "width",
+    "height",
+    "agents",
+    "density",
+    "point_mean",
+    "point_std",
+    "negative_rate",
+    "gap",
+    "turns_left",
+    "progress",
+];
+
+// どの規則にも当てはまらないときに使うソルバー
+const DEFAULT_SOLVER: &str = "social_distance";
+
+// 方針の表を選ぶための盤面の特徴
+pub struct Features {
+    pub width: usize,
+    pub height: usize,
+    pub agents: usize,
+    // 両チームのエージェント数 / マス数
+    pub density: f64,
+    pub point_mean: f64,
+    pub point_std: f64,
+    pub negative_rate: f64,
+    // 自分から見た得点差
+    pub gap: i16,
+    pub turns_left: u8,
+    pub progress: f64,
+}
+
+impl Features {
+    pub fn extract(side: bool, field: &Field) -> Features {
+        let points = (0..field.width())
+            .flat_map(|x| (0..field.height()).map(move |y| PointUsize::new(x, y)))
+            .map(|pos| field.tile(pos).point() as f64)
+            .collect::<Vec<_>>();
+        let n = points.len().max(1) as f64;
+        let mean = points.iter().sum::<f64>() / n;
+        let var = points.iter().fold(0.0, |b, p| b + (p - mean).powi(2)) / n;
+        Features {
+            width: field.width(),
+            height: field.height(),
+            agents: field.agent_count(),
+            density: (field.agent_count() * 2) as f64 / n,
+            point_mean: mean,
+            point_std: var.sqrt(),
+            negative_rate: points.iter().filter(|p| **p < 0.0).count() as f64 / n,
+            gap: base::diff(side, field),
+            turns_left: field.final_turn().saturating_sub(field.now_turn()),
+            progress: if field.final_turn() == 0 {
+                0.0
+            } else {
+                field.now_turn() as f64 / field.final_turn() as f64
+            },
+        }
+    }
+    pub fn get(&self, name: &str) -> Option<f64> {
+        match name {
+            "width" => Some(self.width as f64),
+            "height" => Some(self.height as f64),
+            "agents" => Some(self.agents as f64),
+            "density" => Some(self.density),
+            "point_mean" => Some(self.point_mean),
+            "point_std" => Some(self.point_std),
+            "negative_rate" => Some(self.negative_rate),
+            "gap" => Some(self.gap as f64),
+            "turns_left" => Some(self.turns_left as f64),
+            "progress" => Some(self.progress),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Features {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let items = FEATURES
+            .iter()
+            .map(|name| format!("{}={:.3}", name, self.get(name).unwrap()))
+            .collect::<Vec<_>>();
+        write!(f, "{}", items.join(" "))
+    }
+}
+
+// when の特徴がすべて [min, max] に入っていれば solvers を重みで混ぜて使う
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Rule {
+    #[serde(default)]
+    pub when: BTreeMap<String, (f64, f64)>,
+    // (ソルバーの指定, 重み)
+    pub solvers: Vec<(String, f64)>,
+}
+
+impl Rule {
+    fn matches(&self, features: &Features) -> bool {
+        self.when
+            .iter()
+            .all(|(name, (min, max))| match features.get(name) {
+                Some(v) => *min <= v && v <= *max,
+                None => false,
+            })
+    }
+}
+
+fn is_meta(spec: &str) -> bool {
+    spec == "meta" || spec.starts_with("meta:")
+}
+
+// 上から順に最初に当てはまった規則を使う
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Policy {
+    pub rules: Vec<Rule>,
+}
+
+// 終盤は読み切り, それ以外は social_distance
+impl Default for Policy {
+    fn default() -> Policy {
+        let mut when = BTreeMap::new();
+        when.insert(
+            "turns_left".to_string(),
+            (0.0, endgame::ENDGAME_TURNS as f64),
+        );
+        Policy {
+            rules: vec![
+                Rule {
+                    when,
+                    solvers: vec![("endgame".to_string(), 1.0)],
+                },
+                Rule {
+                    when: BTreeMap::new(),
+                    solvers: vec![(DEFAULT_SOLVER.to_string(), 1.0)],
+                },
+            ],
+        }
+    }
+}
+
+impl Policy {
+    fn validate(&self) -> Result<(), String> {
+        for (i, rule) in self.rules.iter().enumerate() {
+            if let Some(name) = rule.when.keys().find(|k| !FEATURES.contains(&k.as_str())) {
+                return Err(format!("rule {}: unknown feature {}", i, name));
+            }
+            if rule.solvers.is_empty() {
+                return Err(format!("rule {}: solvers is empty", i));
+            }
+            if rule.solvers.iter().any(|(_, w)| *w <= 0.0) {
+                return Err(format!("rule {}: weight must be positive", i));
+            }
+            // meta の中で meta を作ると方針によっては際限なく入れ子になる
+            if rule.solvers.iter().any(|(spec, _)| is_meta(spec)) {
+                return Err(format!("rule {}: meta cannot be nested", i));
+            }
+        }
+        Ok(())
+    }
+    fn select(&self, features: &Features) -> Option<usize> {
+        self.rules.iter().position(|rule| rule.matches(features))
+    }
+}
+
+pub fn read_policy(path: &str) -> Result<Policy, String> {
+    let fp = File::open(path).map_err(|e| e.to_string())?;
+    let policy: Policy = serde_json::from_reader(fp).map_err(|e| e.to_string())?;
+    policy.validate()?;
+    Ok(policy)
+}
+
+// 盤面の特徴から方針の表に従ってソルバーを選ぶ (複数なら手を重みで投票して混ぜる)
+pub struct Meta {
+    side: bool,
+    policy: Policy,
+    // 方針に出てくるソルバー. 使わないターンも update して相手のモデルなどを保つ
+    solvers: BTreeMap<String, Box<dyn base::Solver>>,
+    // 前のターンに使った規則 (変わったときだけログに出す)
+    last_rule: Option<Option<usize>>,
+    explanation: Option<Explanation>,
+}
+
+impl Meta {
+    pub fn new(side: bool, policy: Policy) -> Result<Meta, String> {
+        policy.validate()?;
+        let mut solvers = BTreeMap::new();
+        let specs = policy
+            .rules
+            .iter()
+            .flat_map(|rule| rule.solvers.iter().map(|x| x.0.clone()))
+            .chain(vec![DEFAULT_SOLVER.to_string()]);
+        for spec in specs {
+            if let Entry::Vacant(e) = solvers.entry(spec) {
+                let solver = registry::make_solver(e.key(), side)?;
+                e.insert(solver);
+            }
+        }
+        Ok(Meta {
+            side,
+            policy,
+            solvers,
+            last_rule: None,
+            explanation: None,
+        })
+    }
+}
+
+impl base::Solver for Meta {
+    fn side(&self) -> bool {
+        self.side
+    }
+    fn solve(&mut self, field: &Field, ctx: &mut SolveContext) -> Vec<Act> {
+        let features = Features::extract(self.side, field);
+        let rule = self.policy.select(&features);
+        let chosen = match rule {
+            Some(i) => self.policy.rules[i].solvers.clone(),
+            None => vec![(DEFAULT_SOLVER.to_string(), 1.0)],
+        };
+        if self.last_rule != Some(rule) {
+            eprintln!(
+                "meta: turn {} rule {:?} -> {:?} ({})",
+                field.now_turn(),
+                rule,
+                chosen,
+                features
+            );
+            self.last_rule = Some(rule);
+        }
+
+        let (acts, mut explanation) = if chosen.len() == 1 {
+            let solver = self.solvers.get_mut(&chosen[0].0).unwrap();
+            let acts = solver.solve(field, ctx);
+            let explanation = solver
+                .explain()
+                .cloned()
+                .unwrap_or_else(|| Explanation::new(&chosen[0].0, field, &acts));
+            (acts, explanation)
+        } else {
+            // 残り時間を重みで分け, 各エージェントの手に選んだソルバーの重みを投票する
+            let mut votes = vec![HashMap::new(); field.agent_count()];
+            let mut rest = chosen.iter().map(|x| x.1).sum::<f64>();
+            for (spec, weight) in &chosen {
+                let mut child = ctx.split(ctx.remaining() * weight / rest);
+                rest -= weight;
+                let acts = self.solvers.get_mut(spec).unwrap().solve(field, &mut child);
+                for (id, act) in acts.into_iter().enumerate() {
+                    *votes[id].entry(act).or_insert(0.0) += weight;
+                }
+            }
+            let acts = assignment::assign(self.side, &votes, field);
+            let explanation = Explanation::from_scores("meta", field, &votes, &acts);
+            (acts, explanation)
+        };
+        explanation.add_stat("rule", rule.map_or(-1.0, |i| i as f64));
+        for name in FEATURES.iter() {
+            explanation.add_stat(name, features.get(name).unwrap());
+        }
+        self.explanation = Some(explanation);
+        acts
+    }
+    fn update(&mut self, field: &Field, resolved: &[Vec<Act>]) {
+        for solver in self.solvers.values_mut() {
+            solver.update(field, resolved);
+        }
+    }
+    fn explain(&self) -> Option<&Explanation> {
+        self.explanation.as_ref()
+    }
+}
@@ -1,11 +1,38 @@
+pub mod assignment;
+pub mod attack;
 pub mod base;
+pub mod context;
+pub mod cooperative;
+pub mod defense;
+pub mod enclosure;
+pub mod endgame;
+pub mod equilibrium;
+pub mod explain;
+pub mod ghost;
 pub mod greedy_select;
+pub mod influence;
+pub mod mcts;
+pub mod meta;
+pub mod opponent;
+pub mod pathfind;
+pub mod placement;
+pub mod registry;
+pub mod roles;
 pub mod simple_dp;
 pub mod simple_regret;
 pub mod social_distance;
+pub mod validate;
 
 pub use base::Solver;
+pub use context::SolveContext;
+pub use cooperative::Cooperative;
+pub use endgame::Endgame;
+pub use explain::Explanation;
+pub use ghost::Ghost;
 pub use greedy_select::GreedySelect;
+pub use mcts::Mcts;
+pub use meta::Meta;
+pub use registry::make_solver;
 pub use simple_dp::SimpleDp;
 pub use simple_regret::SimpleRegret;
-pub use social_distance::SocialDistance;
\ No newline at end of file
+pub use social_distance::SocialDistance;
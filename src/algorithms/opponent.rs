@@ -0,0 +1,198 @@
+use super::base;
+use super::pathfind::dist;
+use crate::field;
+use crate::simulator;
+
+use field::{Field, Point, State};
+use simulator::Act;
+use std::collections::HashMap;
+
+// 貪欲さを表す softmax の温度の候補 (小さいほど点の高い手を選ぶ)
+const TEMPS: [f64; 6] = [0.5, 1.0, 2.0, 4.0, 8.0, 16.0];
+const DEFAULT_TEMP: usize = 2;
+const KINDS: usize = 5;
+// 同じ向きに動き続ける傾向の補正の範囲
+const MOMENTUM_MIN: f64 = 0.5;
+const MOMENTUM_MAX: f64 = 4.0;
+
+// 試合中に観測した相手の手から, 次のターンの各エージェントの手の分布を推定する
+pub struct OpponentModel {
+    // 相手の side
+    side: bool,
+    prev: Option<Field>,
+    // 温度ごとの観測した手の対数尤度
+    log_lik: Vec<f64>,
+    // 手の種類ごとの (選んだ回数, 一様に選んだときの期待回数)
+    kinds: [(f64, f64); KINDS],
+    // (前と同じ向きに動いた回数, 前に動いていて今回も動いた回数)
+    momentum: (f64, f64),
+    last_dir: Vec<Option<Point>>,
+}
+
+// 停止, 移動, 自陣の壁への移動, 相手 (こちら) の壁の除去, 自陣の壁の除去
+fn kind(side: bool, act: &Act, field: &Field) -> usize {
+    match act {
+        Act::StayAct | Act::PutAct(_) => 0,
+        Act::MoveAct(p) => match field.tile(p.usize()).state() {
+            State::Wall(_) => 2,
+            _ => 1,
+        },
+        Act::RemoveAct(p) => match field.tile(p.usize()).state() {
+            State::Wall(s) if s == side => 4,
+            _ => 3,
+        },
+    }
+}
+
+fn direction(from: Point, act: &Act) -> Option<Point> {
+    match act {
+        Act::MoveAct(p) => Some(Point::new(p.x - from.x, p.y - from.y)),
+        _ => None,
+    }
+}
+
+impl OpponentModel {
+    pub fn new(side: bool) -> OpponentModel {
+        // 観測がないうちは DEFAULT_TEMP を選ぶ
+        let log_lik = (0..TEMPS.len())
+            .map(|i| -((i as f64) - DEFAULT_TEMP as f64).abs() * 1e-3)
+            .collect();
+        OpponentModel {
+            side,
+            prev: None,
+            log_lik,
+            kinds: [(0.0, 0.0); KINDS],
+            momentum: (0.0, 0.0),
+            last_dir: Vec::new(),
+        }
+    }
+    fn temp(&self) -> f64 {
+        let best = (0..TEMPS.len())
+            .max_by(|a, b| self.log_lik[*a].partial_cmp(&self.log_lik[*b]).unwrap())
+            .unwrap();
+        TEMPS[best]
+    }
+    // 手の種類を一様に選ぶときに比べて, 何倍選びやすいか
+    fn kind_weight(&self, kind: usize) -> f64 {
+        let (count, expected) = self.kinds[kind];
+        (count + 1.0) / (expected + 1.0)
+    }
+    fn momentum_weight(&self) -> f64 {
+        let (same, total) = self.momentum;
+        ((same + 1.0) / (total + 8.0) * 8.0).clamp(MOMENTUM_MIN, MOMENTUM_MAX)
+    }
+    fn candidates(&self, field: &Field, id: usize) -> Vec<(Act, f64)> {
+        base::make_acts(self.side, id, field)
+            .into_iter()
+            .filter_map(|act| match act {
+                Act::StayAct => Some((act, 0.0)),
+                _ => base::point(self.side, act.clone(), field).map(|p| (act, p as f64)),
+            })
+            .collect()
+    }
+    // 前のターンの盤面で相手が実際に打った手を観測する
+    fn observe(&mut self, field: &Field, resolved: &[Vec<Act>]) {
+        self.last_dir.resize(field.agent_count(), None);
+        let stayed = (0..field.agent_count())
+            .flat_map(|id| [(false, id), (true, id)])
+            .filter(|(side, id)| resolved[*side as usize][*id] == Act::StayAct)
+            .filter_map(|(side, id)| field.agent(side, id).map(|pos| (side, id, pos)))
+            .collect::<Vec<_>>();
+        for (id, act) in resolved[self.side as usize].iter().enumerate() {
+            let pos = match field.agent(self.side, id) {
+                Some(pos) => pos,
+                // 配置は対象のマスが多すぎるので数えない
+                None => continue,
+            };
+            // 競合で無効になった手も停止として返ってくるので, 近くに同じく止まったエージェントが
+            // いて同じマスを狙っていた可能性がある停止は数えない
+            if *act == Act::StayAct
+                && stayed
+                    .iter()
+                    .any(|(s, i, p)| (*s, *i) != (self.side, id) && dist(pos, *p) <= 2)
+            {
+                self.last_dir[id] = None;
+                continue;
+            }
+            let cand = self.candidates(field, id);
+            let point = match cand.iter().find(|x| x.0 == *act) {
+                Some(x) => x.1,
+                None => continue,
+            };
+            let max = cand.iter().fold(f64::MIN, |b, x| b.max(x.1));
+            for (i, t) in TEMPS.iter().enumerate() {
+                let sum = cand.iter().fold(0.0, |b, x| b + ((x.1 - max) / t).exp());
+                self.log_lik[i] += (point - max) / t - sum.ln();
+            }
+            for (a, _) in &cand {
+                self.kinds[kind(self.side, a, field)].1 += 1.0 / cand.len() as f64;
+            }
+            self.kinds[kind(self.side, act, field)].0 += 1.0;
+
+            let dir = direction(pos, act);
+            if let (Some(last), Some(dir)) = (self.last_dir[id], dir) {
+                self.momentum.1 += 1.0;
+                if last == dir {
+                    self.momentum.0 += 1.0;
+                }
+            }
+            self.last_dir[id] = dir;
+        }
+    }
+    // 手を考える前の盤面を受け取る. 最初のターンの手もその盤面から観測できるようにする
+    pub fn start(&mut self, field: &Field) {
+        if self.prev.is_none() {
+            self.prev = Some(field.clone());
+        }
+    }
+    // Solver::update から, 手を打った後の盤面と実際に適用された両チームの手を受け取る
+    pub fn update(&mut self, field: &Field, resolved: &[Vec<Act>]) {
+        if let Some(prev) = self.prev.take() {
+            self.observe(&prev, resolved);
+        }
+        self.prev = Some(field.clone());
+    }
+    // 相手の各エージェントの次の手の確率 (未配置のエージェントは空)
+    pub fn predict(&self, field: &Field) -> Vec<HashMap<Act, f64>> {
+        let temp = self.temp();
+        (0..field.agent_count())
+            .map(|id| {
+                let pos = match field.agent(self.side, id) {
+                    Some(pos) => pos,
+                    None => return HashMap::new(),
+                };
+                let cand = self.candidates(field, id);
+                let max = cand.iter().fold(f64::MIN, |b, x| b.max(x.1));
+                let last = self.last_dir.get(id).cloned().flatten();
+                let weights = cand
+                    .iter()
+                    .map(|(act, point)| {
+                        let mut w = ((point - max) / temp).exp()
+                            * self.kind_weight(kind(self.side, act, field));
+                        if last.is_some() && direction(pos, act) == last {
+                            w *= self.momentum_weight();
+                        }
+                        w
+                    })
+                    .collect::<Vec<_>>();
+                let sum = weights.iter().sum::<f64>();
+                cand.into_iter()
+                    .zip(weights)
+                    .map(|((act, _), w)| (act, w / sum))
+                    .collect()
+            })
+            .collect()
+    }
+    // 相手のいずれかのエージェントが次に移動・除去の対象にする確率をマスごとに求める
+    pub fn conflict(&self, field: &Field) -> HashMap<Point, f64> {
+        let mut stay = HashMap::new();
+        for dist in self.predict(field) {
+            for (act, p) in dist {
+                if let Act::MoveAct(pos) | Act::RemoveAct(pos) = act {
+                    *stay.entry(pos).or_insert(1.0) *= 1.0 - p;
+                }
+            }
+        }
+        stay.into_iter().map(|(pos, s)| (pos, 1.0 - s)).collect()
+    }
+}
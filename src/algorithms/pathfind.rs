@@ -0,0 +1,222 @@
+use super::base;
+use crate::field;
+use crate::simulator;
+
+use field::{Field, Point, State};
+use num_traits::pow;
+use simulator::Act;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+// 壁を無視した 8 近傍での距離 (見積もり用). 壁の除去も数えるなら distance_map を使う
+pub fn dist(p: Point, q: Point) -> usize {
+    (p.x - q.x).abs().max((p.y - q.y).abs()) as usize
+}
+
+// 隣のマスへ 1 つ進む手. 相手の壁は除去してから移動するので 2 ターンかかる
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    pub act: Act,
+    pub to: Point,
+    pub turns: usize,
+}
+
+impl Step {
+    // t ターン目から進めたときの割引後の得点と次のターン
+    // 除去は除去したターンと移動したターンの 2 回数え, horizon で打ち切る
+    pub fn gain(&self, point: f64, t: usize, horizon: usize, per: f64) -> (f64, usize) {
+        let turns = self.turns.min(horizon.saturating_sub(t)).max(1);
+        let weight = (0..turns).fold(0.0, |b, k| b + pow(per, k));
+        (point * weight * pow(per, t), t + turns)
+    }
+}
+
+pub fn steps(side: bool, field: &Field, pos: Point) -> Vec<Step> {
+    base::make_neighbors(pos, field)
+        .into_iter()
+        .map(|to| match field.tile(to.usize()).state() {
+            State::Wall(side_) if side != side_ => Step {
+                act: Act::RemoveAct(to),
+                to,
+                turns: 2,
+            },
+            _ => Step {
+                act: Act::MoveAct(to),
+                to,
+                turns: 1,
+            },
+        })
+        .collect()
+}
+
+// いずれかの始点からの各マスへの最短ターン数と, 経路を復元するための直前のマス
+pub struct DistanceMap {
+    width: usize,
+    dist: Vec<Option<usize>>,
+    prev: Vec<Option<Point>>,
+}
+
+impl DistanceMap {
+    fn index(&self, pos: Point) -> usize {
+        pos.y as usize * self.width + pos.x as usize
+    }
+    pub fn get(&self, pos: Point) -> Option<usize> {
+        self.dist[self.index(pos)]
+    }
+    pub fn distances(&self) -> &Vec<Option<usize>> {
+        &self.dist
+    }
+    // 相手の壁 pos を除去し終えるまでのターン数 (除去した後に上へ移動する 1 ターンは数えない)
+    pub fn removal(&self, pos: Point) -> Option<usize> {
+        self.get(pos).map(|d| d.saturating_sub(1))
+    }
+    // 最も近い始点から pos までの手 (始点なら空)
+    pub fn path(&self, side: bool, field: &Field, pos: Point) -> Option<Vec<Step>> {
+        self.get(pos)?;
+        let mut res = Vec::new();
+        let mut now = pos;
+        while let Some(prev) = self.prev[self.index(now)] {
+            let step = steps(side, field, prev)
+                .into_iter()
+                .find(|s| s.to == now)
+                .unwrap();
+            res.push(step);
+            now = prev;
+        }
+        res.reverse();
+        Some(res)
+    }
+}
+
+// sources からの最短ターン数 (horizon を超えるマスは None)
+pub fn distance_map(side: bool, field: &Field, sources: &[Point], horizon: usize) -> DistanceMap {
+    let size = field.width() * field.height();
+    // 遠回りしても全マスを 2 ターンずつで回れる
+    let horizon = horizon.min(size * 2);
+    let mut res = DistanceMap {
+        width: field.width(),
+        dist: vec![None; size],
+        prev: vec![None; size],
+    };
+    // コストは 1 か 2 なので, ターン数ごとの待ち行列で足りる
+    let mut que = vec![VecDeque::new(); horizon + 1];
+    for pos in sources {
+        let i = res.index(*pos);
+        res.dist[i] = Some(0);
+        que[0].push_back(*pos);
+    }
+    for t in 0..=horizon {
+        while let Some(now) = que[t].pop_front() {
+            if res.get(now) != Some(t) {
+                continue;
+            }
+            for step in steps(side, field, now) {
+                let nt = t + step.turns;
+                let i = res.index(step.to);
+                if nt <= horizon && res.dist[i].map_or(true, |x| nt < x) {
+                    res.dist[i] = Some(nt);
+                    res.prev[i] = Some(now);
+                    que[nt].push_back(step.to);
+                }
+            }
+        }
+    }
+    res
+}
+
+// [始点][終点] の最短ターン数. マスの番号は y * width + x
+pub fn all_distances(side: bool, field: &Field) -> Vec<Vec<Option<usize>>> {
+    let mut res = Vec::new();
+    for y in 0..field.height() {
+        for x in 0..field.width() {
+            let pos = Point::new(x as i8, y as i8);
+            res.push(distance_map(side, field, &[pos], usize::MAX).dist);
+        }
+    }
+    res
+}
+
+pub fn shortest_path(side: bool, field: &Field, from: Point, to: Point) -> Option<Vec<Step>> {
+    distance_map(side, field, &[from], usize::MAX).path(side, field, to)
+}
+
+// best_path で通れる手と得点を呼び出し側が決めるための差し込み口. 既定では制限しない
+pub trait PathRules {
+    // pos から t ターン目に step を打ち, nt ターン目に着く手を使わないか
+    fn blocked(&self, _pos: Point, _step: &Step, _t: usize, _nt: usize) -> bool {
+        false
+    }
+    // pos から to へ進んで得る点 (同じ経路で通ったマスには呼ばれない)
+    fn value(&self, _pos: Point, _to: Point, point: f64) -> f64 {
+        point
+    }
+    // nt ターン目に pos で経路を終えてよいか
+    fn can_stop(&self, _pos: Point, _nt: usize) -> bool {
+        true
+    }
+}
+
+impl PathRules for () {}
+
+#[derive(Clone)]
+struct PathState {
+    score: f64,
+    used: HashSet<Point>,
+    steps: Vec<Step>,
+}
+
+// horizon ターンで集められる得点 (per で割引, 同じマスは 1 度だけ) が最大の経路
+// 各ターン・各マスで最も良い状態だけを残す
+pub fn best_path(
+    side: bool,
+    field: &Field,
+    start: Point,
+    horizon: usize,
+    per: f64,
+    rules: &impl PathRules,
+) -> (f64, Vec<Step>) {
+    let mut dp = vec![HashMap::new(); horizon + 1];
+    let mut used = HashSet::new();
+    used.insert(start);
+    dp[0].insert(
+        start,
+        PathState {
+            score: 0.0,
+            used,
+            steps: Vec::new(),
+        },
+    );
+    let mut best = (0.0, Vec::new());
+    for t in 0..horizon {
+        let mut states = dp[t].clone().into_iter().collect::<Vec<_>>();
+        states.sort_by_key(|x| x.0);
+        for (pos, now) in states {
+            for step in steps(side, field, pos) {
+                let point = match base::point(side, step.act.clone(), field) {
+                    Some(_) if now.used.contains(&step.to) => 0.0,
+                    Some(point) => rules.value(pos, step.to, point as f64),
+                    None => continue,
+                };
+                let (add, nt) = step.gain(point, t, horizon, per);
+                if rules.blocked(pos, &step, t, nt) {
+                    continue;
+                }
+                let score = now.score + add;
+                if dp[nt]
+                    .get(&step.to)
+                    .map_or(false, |s: &PathState| s.score >= score)
+                {
+                    continue;
+                }
+                let mut nex = now.clone();
+                nex.score = score;
+                nex.used.insert(step.to);
+                nex.steps.push(step.clone());
+                if score > best.0 && rules.can_stop(step.to, nt) {
+                    best = (score, nex.steps.clone());
+                }
+                dp[nt].insert(step.to, nex);
+            }
+        }
+    }
+    best
+}
@@ -0,0 +1,142 @@
+use super::pathfind::dist;
+use crate::field;
+
+use field::{Field, Point, PointUsize, State};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use std::collections::HashSet;
+
+// 配置したマスから何マス先までの得点を見込むか
+const RADIUS: usize = 3;
+const DECAY: f64 = 0.7;
+// 既に自分のエージェントが近くにいるマスの見込みの割合
+const SPREAD_RATE: f64 = 0.3;
+// 相手のエージェントの方が近いマスの見込みの割合
+const CONTEST_RATE: f64 = 0.5;
+// 隣の負のマスは囲えば陣地点になるので, その絶対値をこの割合だけ足す
+const ENCLOSE_PER: f64 = 0.3;
+// 最良の見込みのこの割合以上のマスからランダムに選ぶ
+const PICK_RATE: f64 = 0.9;
+
+// 歩いて壁にしたときに増える点の見込み
+pub fn gain(side: bool, field: &Field, pos: Point) -> f64 {
+    let tile = field.tile(pos.usize());
+    let point = tile.point() as f64;
+    match tile.state() {
+        State::Neutral => point.max(0.0),
+        State::Position(s) if s != side => point + point.abs(),
+        // 相手の壁は除去してから歩くので半分
+        State::Wall(s) if s != side => point.max(0.0) * 0.5,
+        _ => 0.0,
+    }
+}
+
+fn placeable(side: bool, field: &Field, pos: Point, occupied: &HashSet<Point>) -> bool {
+    field.tile(pos.usize()).state() != State::Wall(!side) && !occupied.contains(&pos)
+}
+
+// 自分と相手のエージェント (予想した配置を含む) から見た, pos に配置したときの見込み
+fn evaluate(side: bool, field: &Field, pos: Point, ours: &[Point], theirs: &[Point]) -> f64 {
+    let mut res = 0.0;
+    let r = RADIUS as i8;
+    for dx in -r..=r {
+        for dy in -r..=r {
+            let q = Point::new(pos.x + dx, pos.y + dy);
+            if q == pos || !field.inside(q) {
+                continue;
+            }
+            let d = dist(pos, q);
+            let mut value = gain(side, field, q) * DECAY.powi(d as i32 - 1);
+            if d == 1 && field.tile(q.usize()).state() == State::Neutral {
+                value += ENCLOSE_PER * (field.tile(q.usize()).point() as f64).min(0.0).abs();
+            }
+            if ours.iter().any(|a| dist(*a, q) <= RADIUS) {
+                value *= SPREAD_RATE;
+            }
+            if theirs.iter().any(|a| dist(*a, q) < d) {
+                value *= CONTEST_RATE;
+            }
+            res += value;
+        }
+    }
+    res
+}
+
+// rng があれば最良に近いマスからランダムに選ぶ (同じ方法で配置する相手と同じマスを取り合わないように)
+fn greedy(
+    side: bool,
+    field: &Field,
+    ids: &[usize],
+    theirs: &[Point],
+    avoid: &HashSet<Point>,
+    mut rng: Option<&mut StdRng>,
+) -> Vec<(usize, Point)> {
+    let mut ours = (0..field.agent_count())
+        .filter_map(|id| field.agent(side, id))
+        .collect::<Vec<_>>();
+    let mut occupied = ours
+        .iter()
+        .chain(theirs)
+        .chain(avoid)
+        .cloned()
+        .collect::<HashSet<_>>();
+    let mut res = Vec::new();
+    for id in ids {
+        let cand = (0..field.width())
+            .flat_map(|x| (0..field.height()).map(move |y| PointUsize::new(x, y).normal()))
+            .filter(|pos| placeable(side, field, *pos, &occupied))
+            .map(|pos| (evaluate(side, field, pos, &ours, theirs), pos))
+            .collect::<Vec<_>>();
+        let best = cand
+            .iter()
+            .max_by(|a, b| a.0.partial_cmp(&b.0).unwrap().then(b.1.cmp(&a.1)))
+            .cloned();
+        let pick = match (&mut rng, best) {
+            (Some(rng), Some((value, _))) => cand
+                .iter()
+                .filter(|x| x.0 >= value * PICK_RATE)
+                .collect::<Vec<_>>()
+                .choose(rng)
+                .map(|x| **x),
+            _ => best,
+        };
+        if let Some((_, pos)) = pick {
+            ours.push(pos);
+            occupied.insert(pos);
+            res.push((*id, pos));
+        }
+    }
+    res
+}
+
+fn unplaced(side: bool, field: &Field) -> Vec<usize> {
+    (0..field.agent_count())
+        .filter(|id| field.agent(side, *id).is_none())
+        .collect()
+}
+
+// 未配置の自分のエージェント全員の配置 (id, マス) を決める.
+// 相手も未配置なら同じ方法で相手の配置を予想し, そのマスは避けて近くは競合するものとして割り引く
+pub fn plan(side: bool, field: &Field, rng: &mut StdRng) -> Vec<(usize, Point)> {
+    let ids = unplaced(side, field);
+    if ids.is_empty() {
+        return Vec::new();
+    }
+    let placed = |side: bool| {
+        (0..field.agent_count())
+            .filter_map(|id| field.agent(side, id))
+            .collect::<Vec<_>>()
+    };
+    let predicted = greedy(
+        !side,
+        field,
+        &unplaced(!side, field),
+        &placed(side),
+        &HashSet::new(),
+        None,
+    );
+    let mut theirs = placed(!side);
+    theirs.extend(predicted.iter().map(|x| x.1));
+    let avoid = predicted.iter().map(|x| x.1).collect::<HashSet<_>>();
+    greedy(side, field, &ids, &theirs, &avoid, Some(rng))
+}
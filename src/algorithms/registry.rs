@@ -0,0 +1,107 @@
+use super::base::Solver;
+use super::meta::{self, Meta, Policy};
+use super::{
+    Cooperative, Endgame, Ghost, GreedySelect, Mcts, SimpleDp, SimpleRegret, SocialDistance,
+};
+use crate::api::parse;
+use crate::record::GameRecord;
+
+type Factory = fn(bool, Option<&str>) -> Result<Box<dyn Solver>, String>;
+
+// (名前, 引数が必須か, 生成関数)
+const SOLVERS: [(&str, bool, Factory); 9] = [
+    ("greedy_select", false, make_greedy_select),
+    ("simple_dp", false, make_simple_dp),
+    ("simple_regret", false, make_simple_regret),
+    ("social_distance", false, make_social_distance),
+    ("mcts", false, make_mcts),
+    ("endgame", false, make_endgame),
+    ("cooperative", false, make_cooperative),
+    ("meta", false, make_meta),
+    ("ghost", true, make_ghost),
+];
+
+const DEFAULT_FALLBACK: &str = "social_distance";
+
+fn make_greedy_select(side: bool, _arg: Option<&str>) -> Result<Box<dyn Solver>, String> {
+    Ok(Box::new(GreedySelect::new(side)))
+}
+
+fn make_simple_dp(side: bool, _arg: Option<&str>) -> Result<Box<dyn Solver>, String> {
+    Ok(Box::new(SimpleDp::new(side)))
+}
+
+fn make_simple_regret(side: bool, _arg: Option<&str>) -> Result<Box<dyn Solver>, String> {
+    Ok(Box::new(SimpleRegret::new(side)))
+}
+
+fn make_mcts(side: bool, _arg: Option<&str>) -> Result<Box<dyn Solver>, String> {
+    Ok(Box::new(Mcts::new(side)))
+}
+
+fn make_endgame(side: bool, _arg: Option<&str>) -> Result<Box<dyn Solver>, String> {
+    Ok(Box::new(Endgame::new(side)))
+}
+
+fn make_cooperative(side: bool, _arg: Option<&str>) -> Result<Box<dyn Solver>, String> {
+    Ok(Box::new(Cooperative::new(side)))
+}
+
+// meta または meta:policy.json
+fn make_meta(side: bool, arg: Option<&str>) -> Result<Box<dyn Solver>, String> {
+    let policy = match arg {
+        Some(path) => meta::read_policy(path)?,
+        None => Policy::default(),
+    };
+    Ok(Box::new(Meta::new(side, policy)?))
+}
+
+// social_distance:params.json
+fn make_social_distance(side: bool, arg: Option<&str>) -> Result<Box<dyn Solver>, String> {
+    let mut solver = SocialDistance::new(side);
+    if let Some(path) = arg {
+        solver.set_schedule(parse::read_param_schedule(path)?);
+    }
+    Ok(Box::new(solver))
+}
+
+// ghost:record.json または ghost:record.json:<fallback の指定>
+fn make_ghost(side: bool, arg: Option<&str>) -> Result<Box<dyn Solver>, String> {
+    let arg = arg.ok_or("ghost needs a record path")?;
+    let mut it = arg.splitn(2, ':');
+    let record = GameRecord::load(it.next().unwrap())?;
+    let fallback = make_solver(it.next().unwrap_or(DEFAULT_FALLBACK), side)?;
+    Ok(Box::new(Ghost::new(side, record, fallback)))
+}
+
+fn split(spec: &str) -> (&str, Option<&str>) {
+    let mut it = spec.splitn(2, ':');
+    (it.next().unwrap(), it.next())
+}
+
+pub fn make_solver(spec: &str, side: bool) -> Result<Box<dyn Solver>, String> {
+    let (name, arg) = split(spec);
+    match SOLVERS.iter().find(|(n, _, _)| *n == name) {
+        Some((_, _, factory)) => factory(side, arg),
+        None => Err(format!("unknown solver {}", name)),
+    }
+}
+
+// 引数なしで作れるソルバーの一覧 (visualizer のボタン用)
+pub fn names() -> Vec<&'static str> {
+    SOLVERS
+        .iter()
+        .filter(|(_, need_arg, _)| !need_arg)
+        .map(|(name, _, _)| *name)
+        .collect()
+}
+
+pub fn params(spec: &str) -> Result<Option<parse::ParamSchedule>, String> {
+    match split(spec) {
+        ("social_distance", Some(path)) => Ok(Some(parse::read_param_schedule(path)?)),
+        ("social_distance", None) => Ok(Some(parse::ParamSchedule::constant(
+            &parse::Params::default(),
+        ))),
+        _ => Ok(None),
+    }
+}
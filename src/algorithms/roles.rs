@@ -0,0 +1,356 @@
+use super::attack;
+use super::defense;
+use super::pathfind::{self, dist, DistanceMap};
+use super::placement;
+use crate::field;
+
+use field::{Field, Point};
+use serde::{Deserialize, Serialize};
+
+// 重み付きボロノイ分割の重みを調整する回数と 1 回の強さ
+const BALANCE_ITER: usize = 8;
+const BALANCE_STEP: f64 = 0.5;
+const WEIGHT_MIN: f64 = 0.25;
+const WEIGHT_MAX: f64 = 4.0;
+// 攻め・守りに回すエージェントの割合の上限
+const RAIDER_RATE: f64 = 0.25;
+const DEFENDER_RATE: f64 = 0.25;
+const THREAT_TURNS: usize = 2;
+// 前のターンと同じ役割なら見込みをこの倍率で評価する (役割が頻繁に入れ替わらないように)
+const KEEP_RATE: f64 = 1.3;
+// 候補の手の得点にかける倍率
+const OUT_OF_ZONE_PER: f64 = 0.85;
+const OFF_TARGET_PER: f64 = 0.85;
+const ON_TARGET_PER: f64 = 1.2;
+// 守りは目標の壁からこの距離までは自由に動く
+const DEFEND_RADIUS: usize = 2;
+// 守りに付きっきりになるターン数の見込み (壁の点をこれで割って 1 ターンあたりにする)
+const DEFEND_TURNS: usize = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Role {
+    // 担当の区域で壁を広げる
+    Builder,
+    // 相手の陣地を開ける壁を除去しに行く
+    Raider,
+    // 除去されそうな自分の壁を守る
+    Defender,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Assignment {
+    pub role: Role,
+    // 攻め・守りの目標の壁
+    pub target: Option<Point>,
+    // 役割を選んだときの見込み (1 ターンあたりの点)
+    pub value: f64,
+}
+
+// 各マスを最も早く着けるエージェントの区域に分け, 区域の見込みが偏らないように重みで調整する
+pub struct Zones {
+    width: usize,
+    // マスごとの担当のエージェントの id
+    owner: Vec<Option<usize>>,
+    // エージェントの id ごとの区域の見込みの合計と, 見込みのあるマスの数
+    value: Vec<f64>,
+    tiles: Vec<usize>,
+}
+
+impl Zones {
+    // 各エージェントから各マスへの最短ターン数
+    fn distance_maps(side: bool, field: &Field, ids: &[usize]) -> Vec<DistanceMap> {
+        ids.iter()
+            .map(|id| {
+                let pos = field.agent(side, *id).unwrap();
+                pathfind::distance_map(side, field, &[pos], usize::MAX)
+            })
+            .collect()
+    }
+    fn gains(side: bool, field: &Field) -> Vec<f64> {
+        (0..field.width() * field.height())
+            .map(|i| {
+                let pos = Point::new((i % field.width()) as i8, (i / field.width()) as i8);
+                placement::gain(side, field, pos)
+            })
+            .collect()
+    }
+    // 重みを調整しながら, 見込みが最も大きい区域が最も小さくなった分け方とそのときの重みを返す
+    fn balance(
+        field: &Field,
+        ids: &[usize],
+        maps: &[&DistanceMap],
+        gains: &[f64],
+    ) -> (Zones, Vec<f64>) {
+        let mut weights = vec![1.0; ids.len()];
+        let mut best: Option<(Zones, Vec<f64>)> = None;
+        for _ in 0..=BALANCE_ITER {
+            let now = Zones::assign(field, ids, maps, gains, &weights);
+            let mean = ids.iter().map(|id| now.value[*id]).sum::<f64>() / ids.len().max(1) as f64;
+            let used = weights.clone();
+            // 見込みの大きい区域は重みを上げて狭くする
+            for (k, id) in ids.iter().enumerate() {
+                let rate = (now.value[*id] / mean).max(0.1);
+                weights[k] = (weights[k] * rate.powf(BALANCE_STEP)).clamp(WEIGHT_MIN, WEIGHT_MAX);
+            }
+            let done = mean <= 0.0;
+            if best
+                .as_ref()
+                .map_or(true, |b| now.max_value(ids) < b.0.max_value(ids))
+            {
+                best = Some((now, used));
+            }
+            if done {
+                break;
+            }
+        }
+        best.unwrap()
+    }
+    // 各マスを (最短ターン数 * 重み) が最も小さいエージェントの区域にする
+    fn assign(
+        field: &Field,
+        ids: &[usize],
+        maps: &[&DistanceMap],
+        gains: &[f64],
+        weights: &[f64],
+    ) -> Zones {
+        let mut res = Zones {
+            width: field.width(),
+            owner: vec![None; gains.len()],
+            value: vec![0.0; field.agent_count()],
+            tiles: vec![0; field.agent_count()],
+        };
+        for (i, gain) in gains.iter().enumerate() {
+            let mut best = None;
+            for (k, map) in maps.iter().enumerate() {
+                if let Some(d) = map.distances()[i] {
+                    let cost = d as f64 * weights[k];
+                    if best.map_or(true, |(c, _)| cost < c) {
+                        best = Some((cost, k));
+                    }
+                }
+            }
+            if let Some((_, k)) = best {
+                res.owner[i] = Some(ids[k]);
+                res.value[ids[k]] += gain;
+                if *gain > 0.0 {
+                    res.tiles[ids[k]] += 1;
+                }
+            }
+        }
+        res
+    }
+    fn max_value(&self, ids: &[usize]) -> f64 {
+        ids.iter().map(|id| self.value[*id]).fold(0.0, f64::max)
+    }
+    pub fn owner(&self, pos: Point) -> Option<usize> {
+        self.owner[pos.y as usize * self.width + pos.x as usize]
+    }
+    pub fn value(&self, id: usize) -> f64 {
+        self.value[id]
+    }
+    // 区域で 1 ターンに見込める点. 1 ターンに 1 マスずつ壁にするものとして, 見込みのあるマスの
+    // 見込みの平均を使う (攻め・守りの Assignment::value と同じ 1 ターンあたりの点)
+    pub fn rate(&self, id: usize) -> f64 {
+        if self.tiles[id] == 0 {
+            0.0
+        } else {
+            self.value[id] / self.tiles[id] as f64
+        }
+    }
+}
+
+// 自分のエージェントの役割と区域. 毎ターン盤面に合わせて決め直す
+pub struct Roles {
+    side: bool,
+    assignments: Vec<Option<Assignment>>,
+    zones: Option<Zones>,
+}
+
+impl Roles {
+    pub fn new(side: bool) -> Roles {
+        Roles {
+            side,
+            assignments: Vec::new(),
+            zones: None,
+        }
+    }
+    pub fn update(&mut self, field: &Field) {
+        let side = self.side;
+        let prev = std::mem::replace(&mut self.assignments, vec![None; field.agent_count()]);
+        let keep = |id: usize, role: Role, target: Option<Point>| match prev.get(id) {
+            Some(Some(a)) if a.role == role && a.target == target => KEEP_RATE,
+            _ => 1.0,
+        };
+        let agents = (0..field.agent_count())
+            .filter(|id| field.agent(side, *id).is_some())
+            .collect::<Vec<_>>();
+        if agents.is_empty() {
+            self.zones = None;
+            return;
+        }
+        let maps = Zones::distance_maps(side, field, &agents);
+        let gains = Zones::gains(side, field);
+        let (all, weights) =
+            Zones::balance(field, &agents, &maps.iter().collect::<Vec<_>>(), &gains);
+
+        // (見込み, id, 役割, 目標)
+        let mut cand = Vec::new();
+        let max_raiders = (agents.len() as f64 * RAIDER_RATE).floor() as usize;
+        for b in attack::propose(side, field, max_raiders) {
+            let (id, _) = b.agent.unwrap();
+            let value = b.efficiency() * keep(id, Role::Raider, Some(b.wall));
+            cand.push((value, id, Role::Raider, b.wall));
+        }
+        for t in defense::detect(side, field, THREAT_TURNS) {
+            // 間に合う中で最も早く着くエージェント
+            if let Some(d) = t
+                .defenders
+                .iter()
+                .filter(|d| d.1 <= t.turns())
+                .min_by_key(|d| (d.1, d.0))
+            {
+                let value = t.stake as f64 / (d.1 + DEFEND_TURNS) as f64
+                    * keep(d.0, Role::Defender, Some(t.wall));
+                cand.push((value, d.0, Role::Defender, t.wall));
+            }
+        }
+        cand.sort_by(|a, b| {
+            b.0.partial_cmp(&a.0)
+                .unwrap()
+                .then(a.1.cmp(&b.1))
+                .then(a.3.cmp(&b.3))
+        });
+        let max_defenders = (agents.len() as f64 * DEFENDER_RATE).floor() as usize;
+        let (mut raiders, mut defenders) = (0, 0);
+        for (value, id, role, target) in cand {
+            let count = match role {
+                Role::Raider => &mut raiders,
+                _ => &mut defenders,
+            };
+            let limit = if role == Role::Raider {
+                max_raiders
+            } else {
+                max_defenders
+            };
+            if self.assignments[id].is_some()
+                || *count >= limit
+                || value <= all.rate(id) * keep(id, Role::Builder, None)
+            {
+                continue;
+            }
+            *count += 1;
+            self.assignments[id] = Some(Assignment {
+                role,
+                target: Some(target),
+                value,
+            });
+        }
+
+        // 残りは区域を担当させる. 攻め・守りに回ったエージェントの区域は, 重みはそのままで
+        // 残りのエージェントに分け直す
+        let (builders, kept): (Vec<_>, Vec<_>) = agents
+            .iter()
+            .zip(maps.iter().zip(weights))
+            .filter(|(id, _)| self.assignments[**id].is_none())
+            .map(|(id, x)| (*id, x))
+            .unzip();
+        let zones = if builders.len() == agents.len() {
+            all
+        } else {
+            let (maps, weights): (Vec<_>, Vec<_>) = kept.into_iter().unzip();
+            Zones::assign(field, &builders, &maps, &gains, &weights)
+        };
+        for id in builders {
+            self.assignments[id] = Some(Assignment {
+                role: Role::Builder,
+                target: None,
+                value: zones.rate(id),
+            });
+        }
+        self.zones = Some(zones);
+    }
+    pub fn assignment(&self, id: usize) -> Option<&Assignment> {
+        self.assignments.get(id).and_then(|x| x.as_ref())
+    }
+    pub fn zones(&self) -> Option<&Zones> {
+        self.zones.as_ref()
+    }
+    // id のエージェントが from から to に進む手の得点を役割に合わせて増減する.
+    // 目標の壁 (自分の壁なら点は 0) に向かう手には役割の見込みを足し, それ以外の負の点はそのまま
+    pub fn adjust(&self, id: usize, from: Point, to: Point, point: f64) -> f64 {
+        let a = match self.assignment(id) {
+            Some(a) => a,
+            None => return point,
+        };
+        if a.target == Some(to) {
+            return point.max(0.0) * ON_TARGET_PER + a.value;
+        }
+        if point <= 0.0 {
+            return point;
+        }
+        let per = match (a.role, a.target) {
+            (Role::Builder, _) => match self.zones().and_then(|z| z.owner(to)) {
+                Some(owner) if owner != id => OUT_OF_ZONE_PER,
+                _ => 1.0,
+            },
+            (Role::Raider, Some(target)) if dist(to, target) < dist(from, target) => 1.0,
+            (Role::Defender, Some(target)) if dist(to, target) <= DEFEND_RADIUS => 1.0,
+            _ => OFF_TARGET_PER,
+        };
+        point * per
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithms::cooperative;
+    use field::{PointUsize, State};
+
+    fn defender(target: Point, value: f64) -> Roles {
+        Roles {
+            side: false,
+            assignments: vec![Some(Assignment {
+                role: Role::Defender,
+                target: Some(target),
+                value,
+            })],
+            zones: None,
+        }
+    }
+
+    #[test]
+    fn defender_prefers_target_wall() {
+        let mut field = Field::from_points(&[&[2, 2, 0], &[2, 2, 2], &[2, 2, 2]], 1);
+        let wall = Point::new(2, 0);
+        field.set_state(PointUsize::new(2, 0), State::Wall(false));
+        field.set_agent(false, 0, Some(Point::new(1, 0)));
+
+        // 自分の壁に乗る手の点は 0 でも, 守りの見込みが足される
+        let roles = defender(wall, 3.0);
+        assert_eq!(roles.adjust(0, Point::new(1, 0), wall, 0.0), 3.0);
+        assert!(
+            roles.adjust(0, Point::new(1, 0), wall, 0.0)
+                > roles.adjust(0, Point::new(1, 0), Point::new(1, 1), 2.0)
+        );
+
+        let plan = cooperative::plan(false, &field, &roles, &[0], 1);
+        assert_eq!(plan.paths[0][0].to, wall);
+        // 役割がなければ点のあるマスに向かう
+        let plan = cooperative::plan(false, &field, &Roles::new(false), &[0], 1);
+        assert_ne!(plan.paths[0][0].to, wall);
+    }
+
+    #[test]
+    fn off_target_penalty_keeps_negative_points() {
+        let roles = defender(Point::new(0, 0), 3.0);
+        assert_eq!(
+            roles.adjust(0, Point::new(3, 3), Point::new(4, 4), 2.0),
+            2.0 * OFF_TARGET_PER
+        );
+        assert_eq!(
+            roles.adjust(0, Point::new(3, 3), Point::new(4, 4), -2.0),
+            -2.0
+        );
+    }
+}
@@ -1,46 +1,55 @@
 use super::base;
+use super::context::SolveContext;
+use super::explain::Explanation;
+use super::pathfind;
 use crate::field;
 use crate::simulator;
 
-use field::{Field, Point, State};
-use num_traits::pow;
+use field::{Field, Point};
 use simulator::Act;
 use std::collections::{HashMap, HashSet};
 
-pub struct SimpleDp<'a> {
-    field: &'a Field,
+pub struct SimpleDp {
     side: bool,
     data: HashMap<Point, f64>,
     agent_set: HashSet<Point>,
+    explanation: Option<Explanation>,
 }
 
 const TURN: u8 = 5;
 const PER: f64 = 0.7;
 const CONFLICT: f64 = 0.3;
 
-impl<'a> base::Solver<'a> for SimpleDp<'a> {
-    fn new(side: bool, field: &'a Field) -> SimpleDp<'a> {
+impl SimpleDp {
+    pub fn new(side: bool) -> SimpleDp {
         SimpleDp {
-            field,
             side,
             data: HashMap::new(),
             agent_set: HashSet::new(),
+            explanation: None,
         }
     }
-    fn field(&self) -> &Field {
-        self.field
-    }
+}
+
+impl base::Solver for SimpleDp {
     fn side(&self) -> bool {
         self.side
     }
-    fn solve(&mut self) -> Vec<Act> {
-        self.calc_dp();
-        for id in 0..self.field.agent_count() {
-            if let Some(pos) = self.field.agent(!self.side, id) {
+    fn solve(&mut self, field: &Field, _ctx: &mut SolveContext) -> Vec<Act> {
+        self.data.clear();
+        self.agent_set.clear();
+        self.calc_dp(field);
+        for id in 0..field.agent_count() {
+            if let Some(pos) = field.agent(!self.side, id) {
                 self.agent_set.insert(pos);
             }
         }
-        base::solve(self)
+        let (acts, explanation) = base::solve(self, field, "simple_dp");
+        self.explanation = Some(explanation);
+        acts
+    }
+    fn explain(&self) -> Option<&Explanation> {
+        self.explanation.as_ref()
     }
 }
 
@@ -77,9 +86,15 @@ impl DpState {
     }
 }
 
-impl SimpleDp<'_> {
-    fn calc_base(&self, now_state: &DpState, nex_pos: &Point, act: &Act) -> Option<f64> {
-        match base::point(self.side, act.clone(), self.field) {
+impl SimpleDp {
+    fn calc_base(
+        &self,
+        field: &Field,
+        now_state: &DpState,
+        nex_pos: &Point,
+        act: &Act,
+    ) -> Option<f64> {
+        match base::point(self.side, act.clone(), field) {
             Some(point) => Some(if now_state.used.contains(nex_pos) {
                 0.0
             } else {
@@ -89,11 +104,11 @@ impl SimpleDp<'_> {
         }
     }
 
-    fn calc_dp(&mut self) {
-        let turn = TURN.min(self.field.final_turn() - self.field.now_turn()) as usize;
+    fn calc_dp(&mut self, field: &Field) {
+        let turn = TURN.min(field.final_turn() - field.now_turn()) as usize;
         let mut dp = vec![HashMap::new(); turn + 1];
-        for i in 0..self.field.width() {
-            for j in 0..self.field.height() {
+        for i in 0..field.width() {
+            for j in 0..field.height() {
                 dp[0].insert(
                     Point::new(i as i8, j as i8),
                     DpState {
@@ -107,42 +122,15 @@ impl SimpleDp<'_> {
             }
         }
         for t in 0..turn {
-            for (pos, now_state) in dp[t].clone() {
+            // 同点のときの選び方が HashMap の順序に依存しないようにする
+            for (pos, now_state) in sorted_states(&dp[t]) {
                 let score = now_state.score;
-                let neighbors = base::make_neighbors(pos, self.field);
-                for nex in neighbors {
-                    let tile = self.field.tile(nex.usize());
-                    if let Some((nex_state, nex_turn)) = match tile.state() {
-                        State::Wall(side_) if self.side != side_ => {
-                            let act = Act::RemoveAct(nex);
-                            if let Some(point) = self.calc_base(&now_state, &nex, &act) {
-                                if t == turn - 1 {
-                                    let nex_score = score + point * pow(PER, t);
-                                    Some((
-                                        DpState::new(act, nex_score, pos, &now_state.used, t),
-                                        t + 1,
-                                    ))
-                                } else {
-                                    let nex_score = score + (point * (1.0 + PER)) * pow(PER, t);
-                                    Some((
-                                        DpState::new(act, nex_score, pos, &now_state.used, t),
-                                        t + 2,
-                                    ))
-                                }
-                            } else {
-                                None
-                            }
-                        }
-                        _ => {
-                            let act = Act::MoveAct(nex);
-                            if let Some(point) = self.calc_base(&now_state, &nex, &act) {
-                                let nex_score = score + point * pow(PER, t);
-                                Some((DpState::new(act, nex_score, pos, &now_state.used, t), t + 1))
-                            } else {
-                                None
-                            }
-                        }
-                    } {
+                for step in pathfind::steps(self.side, field, pos) {
+                    let nex = step.to;
+                    if let Some(point) = self.calc_base(field, &now_state, &nex, &step.act) {
+                        let (add, nex_turn) = step.gain(point, t, turn, PER);
+                        let nex_state =
+                            DpState::new(step.act, score + add, pos, &now_state.used, t);
                         if !dp[nex_turn].contains_key(&nex) {
                             dp[nex_turn].insert(nex, nex_state);
                         } else {
@@ -156,7 +144,7 @@ impl SimpleDp<'_> {
             }
         }
         for t in (1..=turn).rev() {
-            for (pos, now_state) in dp[t].clone() {
+            for (pos, now_state) in sorted_states(&dp[t]) {
                 if now_state.prev_turn.unwrap() == 0 {
                     match self.data.get(&pos) {
                         None => {
@@ -176,19 +164,25 @@ impl SimpleDp<'_> {
     }
 }
 
-impl base::EachEvalSolver for SimpleDp<'_> {
-    fn eval(&self, _id: usize, act: Act) -> Option<f64> {
+fn sorted_states(states: &HashMap<Point, DpState>) -> Vec<(Point, DpState)> {
+    let mut states = states.clone().into_iter().collect::<Vec<_>>();
+    states.sort_by_key(|x| x.0);
+    states
+}
+
+impl base::EachEvalSolver for SimpleDp {
+    fn eval(&self, field: &Field, _id: usize, act: Act) -> Option<f64> {
         match act {
             Act::StayAct => Some(0.0),
             Act::PutAct(pos) | Act::MoveAct(pos) => {
-                if self.field.tile(pos.usize()).state() == field::State::Wall(!self.side) {
+                if field.tile(pos.usize()).state() == field::State::Wall(!self.side) {
                     None
                 } else {
                     self.data.get(&pos).cloned()
                 }
             }
             Act::RemoveAct(pos) => {
-                if self.field.tile(pos.usize()).state() == field::State::Wall(!self.side) {
+                if field.tile(pos.usize()).state() == field::State::Wall(!self.side) {
                     self.data.get(&pos).map(|x| {
                         x * (if self.agent_set.contains(&pos) {
                             CONFLICT
@@ -1,4 +1,7 @@
 use super::base;
+use super::context::SolveContext;
+use super::explain::Explanation;
+use super::opponent::OpponentModel;
 use crate::algorithms;
 use crate::field;
 use crate::simulator;
@@ -7,24 +10,64 @@ use field::Field;
 use simulator::Act;
 
 const NUM_ITER: usize = 100;
+// 収束の具合を見るために平均戦略から引く手の組の数
+const EXPLOIT_SAMPLES: usize = 8;
 
-pub struct SimpleRegret<'a> {
-    field: &'a Field,
+pub struct SimpleRegret {
     side: bool,
+    model: OpponentModel,
+    exploitability: f64,
+    explanation: Option<Explanation>,
 }
 
-impl<'a> base::Solver<'a> for SimpleRegret<'a> {
-    fn new(side: bool, field: &'a Field) -> SimpleRegret<'a> {
-        SimpleRegret { field, side }
+impl SimpleRegret {
+    pub fn new(side: bool) -> SimpleRegret {
+        SimpleRegret {
+            side,
+            model: OpponentModel::new(!side),
+            exploitability: 0.0,
+            explanation: None,
+        }
     }
-    fn field(&self) -> &Field {
-        self.field
+    // 最後に時間内に求められた均衡の exploitability
+    pub fn exploitability(&self) -> f64 {
+        self.exploitability
     }
+}
+
+impl base::Solver for SimpleRegret {
     fn side(&self) -> bool {
         self.side
     }
-    fn solve(&mut self) -> Vec<Act> {
-        // base::solve_regret_matching::<algorithms::GreedySelect>(self.side(), self.field, NUM_ITER)
-        base::solve_regret_matching::<algorithms::SimpleDp>(self.side(), self.field, NUM_ITER)
+    fn solve(&mut self, field: &Field, ctx: &mut SolveContext) -> Vec<Act> {
+        self.model.start(field);
+        let opponent = self.model.predict(field);
+        // base::solve_regret_matching(self.side(), field, ctx, NUM_ITER, algorithms::GreedySelect::new)
+        let (acts, eq, values) = base::solve_regret_matching(
+            self.side(),
+            field,
+            ctx,
+            NUM_ITER,
+            algorithms::SimpleDp::new,
+            Some(&opponent),
+        );
+        if !ctx.timed_out() {
+            self.exploitability = eq.exploitability(field, EXPLOIT_SAMPLES, &mut ctx.rng);
+        }
+        // 候補の値は平均戦略の相手に対する期待利得
+        let mut explanation = Explanation::from_scores("simple_regret", field, &values, &acts);
+        for (id, prob) in eq.strategy(self.side).iter().enumerate() {
+            explanation.add_term(id, "strategy", *prob.get(&acts[id]).unwrap_or(&0.0));
+        }
+        explanation.add_stat("iterations", eq.iterations() as f64);
+        explanation.add_stat("exploitability", self.exploitability);
+        self.explanation = Some(explanation);
+        acts
+    }
+    fn explain(&self) -> Option<&Explanation> {
+        self.explanation.as_ref()
+    }
+    fn update(&mut self, field: &Field, resolved: &[Vec<Act>]) {
+        self.model.update(field, resolved);
     }
 }
@@ -1,26 +1,45 @@
+use super::attack;
 use super::base;
+use super::context::SolveContext;
+use super::defense;
+use super::enclosure;
+use super::endgame::{self, Endgame};
+use super::explain::Explanation;
+use super::influence::Influence;
+use super::opponent::OpponentModel;
+use super::pathfind;
+use super::placement;
+use super::roles::Roles;
 use crate::field;
 use crate::simulator;
 
-use crate::api::parse::Params;
-use crate::field::PointUsize;
+use crate::api::parse::{ParamSchedule, Params};
 use base::MinOrdFloat;
-use field::{Field, Point, State};
-use num_traits::pow;
+use field::{Field, Point, PointUsize, State};
 use rand::Rng;
+use rayon::prelude::*;
 use simulator::Act;
 use std::cmp::Ordering;
 use std::collections::{BTreeSet, HashMap, HashSet};
-use std::time::Instant;
 
 const DEPTH: usize = 5;
 const WIDTH: usize = 10;
-const PUT_WIDTH: usize = 20;
 
 const START_TEMP: f64 = 3.0;
 const END_TEMP: f64 = 0.3;
+// 焼きなまし 1 回の持ち時間. 全体では残り時間の SA_TIME_RATE までに収める
 const SA_SEC: f64 = 0.1;
-const PUT_BORDER: f64 = 1.0;
+const SA_TIME_RATE: f64 = 0.9;
+// 焼きなましの回数 (スレッド数によらず固定)
+const SA_RESTARTS: usize = 8;
+// 反復回数で持ち時間を数えるときの 1 回あたりの反復回数と, 1 反復の重さ (SolveContext::spend)
+const SA_ITER: usize = 4000;
+const SA_STEPS: usize = 25;
+const MAX_ENCLOSURES: usize = 2;
+const MAX_BREACHES: usize = 2;
+const MAX_THREATS: usize = 2;
+// 相手がこのターン数以内に除去できる自分の壁を守る
+const THREAT_TURNS: usize = 2;
 
 /*
 const PER: f64 = 0.6;
@@ -46,48 +65,201 @@ const SAME_TILE_PER: f64 = 1.0;
 const SAME_TILE_POW: f64 = 2.0;
  */
 
-pub struct SocialDistance<'a> {
+pub struct SocialDistance {
+    side: bool,
+    params: ParamSchedule,
+    // 前のターンに選んだ各エージェントの経路 (先頭は現在地)
+    plan: Vec<Vec<Point>>,
+    model: OpponentModel,
+    roles: Roles,
+    explanation: Option<Explanation>,
+}
+
+struct Planner<'a> {
     field: &'a Field,
     agent_set: HashSet<Point>,
     side: bool,
     params: Params,
+    plan: Vec<Vec<Point>>,
+    // 囲いを作るために壁にしたいマス, 相手の陣地を開けるために除去したい壁, 守りたい自分の壁に足す点
+    region_bonus: HashMap<Point, f64>,
+    // 相手のエージェントが次に踏む・除去する確率の予想
+    conflict: HashMap<Point, f64>,
+    // 相手が先に届くか, 同じターンに届いて取り合いになる確率 (Influence)
+    contest: HashMap<Point, f64>,
+    // 候補の経路の得点を各エージェントの役割と区域に合わせて増減する
+    roles: &'a Roles,
 }
 
-enum SaRes {
-    Normal(Vec<Vec<(f64, Act, Vec<Point>)>>),
-    Put((Vec<(f64, Act, Vec<Point>)>, Vec<(f64, Act, Vec<Point>)>)),
+// 手の組の評価の項をエージェントごとに集計する (名前, 値)
+type Terms = Vec<Vec<(&'static str, f64)>>;
+
+fn note(terms: &mut Option<&mut Terms>, i: usize, name: &'static str, value: f64) {
+    if let Some(terms) = terms {
+        match terms[i].iter_mut().find(|x| x.0 == name) {
+            Some(x) => x.1 += value,
+            None => terms[i].push((name, value)),
+        }
+    }
 }
 
-impl SocialDistance<'_> {
+impl SocialDistance {
+    pub fn new(side: bool) -> SocialDistance {
+        SocialDistance {
+            side,
+            params: ParamSchedule::constant(&Params::default()),
+            plan: Vec::new(),
+            model: OpponentModel::new(!side),
+            roles: Roles::new(side),
+            explanation: None,
+        }
+    }
     pub fn set_params(&mut self, params: Params) {
+        self.params = ParamSchedule::constant(&params);
+    }
+    pub fn set_schedule(&mut self, params: ParamSchedule) {
         self.params = params;
     }
 }
 
-impl<'a> base::Solver<'a> for SocialDistance<'a> {
-    fn new(side: bool, field: &'a Field) -> SocialDistance<'a> {
-        SocialDistance {
+impl base::Solver for SocialDistance {
+    fn side(&self) -> bool {
+        self.side
+    }
+    fn solve(&mut self, field: &Field, ctx: &mut SolveContext) -> Vec<Act> {
+        // 残り数ターンは読み切る
+        if endgame::applicable(field) {
+            let mut solver = Endgame::new(self.side);
+            let acts = solver.solve(field, ctx);
+            self.explanation = solver.explain().cloned();
+            return acts;
+        }
+        self.plan.resize(field.agent_count(), Vec::new());
+        let params = self.params.resolve(field);
+        self.model.start(field);
+        self.roles.update(field);
+        let mut planner = Planner {
             field,
-            side,
+            side: self.side,
             agent_set: HashSet::new(),
-            params: Params::default(),
+            region_bonus: region_bonus(self.side, field, &params),
+            conflict: self.model.conflict(field),
+            contest: contest_map(self.side, field),
+            roles: &self.roles,
+            params,
+            plan: self.plan.clone(),
+        };
+        let (acts, explanation) = planner.solve(ctx);
+        self.plan = planner.plan;
+        self.explanation = Some(explanation);
+        acts
+    }
+    // 計画通りに 1 手進めたエージェントだけ経路を引き継ぐ
+    fn update(&mut self, field: &Field, resolved: &[Vec<Act>]) {
+        for (id, path) in self.plan.iter_mut().enumerate() {
+            let next = match &resolved[self.side as usize][id] {
+                Act::MoveAct(p) | Act::RemoveAct(p) => Some(*p),
+                _ => None,
+            };
+            if path.len() >= 2 && next == Some(path[1]) {
+                path.remove(0);
+            } else {
+                path.clear();
+            }
         }
+        self.model.update(field, resolved);
     }
-    fn field(&self) -> &Field {
-        self.field
+    fn explain(&self) -> Option<&Explanation> {
+        self.explanation.as_ref()
     }
-    fn side(&self) -> bool {
-        self.side
+}
+
+// 囲いの計画の得点を, 完成までのターン数で割り引いて残りの壁のマスに配る.
+// 相手の陣地を開けられる壁にも, 開けられる陣地点を同じように割り引いて足す.
+// 間に合うエージェントがいる自分の壁は, 除去されると失う点を上に立つ手に足す.
+// 囲い, 攻め, 守りはそれぞれ別のパラメータで重み付けし, 0 以下なら計算しない
+fn region_bonus(side: bool, field: &Field, params: &Params) -> HashMap<Point, f64> {
+    let mut bonus = HashMap::new();
+    if params.REGION_PER > 0.0 {
+        add_enclosure_bonus(side, field, params, &mut bonus);
+    }
+    if params.ATTACK_PER > 0.0 {
+        add_attack_bonus(side, field, params, &mut bonus);
+    }
+    if params.DEFENSE_PER > 0.0 {
+        add_defense_bonus(side, field, params, &mut bonus);
     }
-    fn solve(&mut self) -> Vec<Act> {
+    bonus
+}
+
+fn add_enclosure_bonus(
+    side: bool,
+    field: &Field,
+    params: &Params,
+    bonus: &mut HashMap<Point, f64>,
+) {
+    for e in enclosure::propose(side, field, MAX_ENCLOSURES) {
+        let share = params.REGION_PER
+            * e.value as f64
+            * params.REGION_POW.powi(e.turns as i32)
+            * (1.0 - e.contest)
+            / e.missing.len() as f64;
+        for pos in e.missing {
+            *bonus.entry(pos).or_insert(0.0) += share;
+        }
+    }
+}
+
+fn add_attack_bonus(side: bool, field: &Field, params: &Params, bonus: &mut HashMap<Point, f64>) {
+    for b in attack::propose(side, field, MAX_BREACHES) {
+        let turns = b.turns().unwrap_or(0);
+        *bonus.entry(b.wall).or_insert(0.0) +=
+            params.ATTACK_PER * b.value as f64 * params.ATTACK_POW.powi(turns as i32);
+    }
+}
+
+fn add_defense_bonus(side: bool, field: &Field, params: &Params, bonus: &mut HashMap<Point, f64>) {
+    for t in defense::detect(side, field, THREAT_TURNS)
+        .into_iter()
+        .filter(|t| t.guarded() && t.defenders.iter().all(|d| d.1 > 0))
+        .take(MAX_THREATS)
+    {
+        *bonus.entry(t.wall).or_insert(0.0) +=
+            params.DEFENSE_PER * t.stake as f64 * params.DEFENSE_POW.powi(t.turns() as i32);
+    }
+}
+
+// 計画の深さまでの到達の分布から, 各マスを相手に取られるか取り合いになる確率を求める
+fn contest_map(side: bool, field: &Field) -> HashMap<Point, f64> {
+    let influence = Influence::new(field, DEPTH);
+    let mut res = HashMap::new();
+    for x in 0..field.width() {
+        for y in 0..field.height() {
+            let pos = PointUsize::new(x, y);
+            let p = influence.control(!side, pos) + influence.contest(pos);
+            if p > 0.0 {
+                res.insert(pos.normal(), p);
+            }
+        }
+    }
+    res
+}
+
+impl Planner<'_> {
+    fn solve(&mut self, ctx: &mut SolveContext) -> (Vec<Act>, Explanation) {
         let mut acts = vec![Act::StayAct; self.field.agent_count()];
         for id in 0..self.field.agent_count() {
             if let Some(pos) = self.field.agent(!self.side, id) {
                 self.agent_set.insert(pos);
             }
         }
-        self.move_confirm(&mut acts);
-        acts
+        let mut explanation = Explanation::new("social_distance", self.field, &acts);
+        self.move_confirm(&mut acts, &mut explanation, ctx);
+        for (agent, act) in explanation.agents.iter_mut().zip(&acts) {
+            agent.chosen = act.clone();
+        }
+        explanation.finish();
+        (acts, explanation)
     }
 }
 
@@ -135,24 +307,26 @@ impl DpState {
     }
 }
 
-impl SocialDistance<'_> {
-    fn calc_score_normal(
+impl Planner<'_> {
+    fn calc_score(
         &self,
-        bs_data: &Vec<Vec<(f64, Act, Vec<Point>)>>,
-        sel: &Vec<usize>,
-        output: bool,
+        bs_data: &[Vec<(f64, Act, Vec<Point>)>],
+        sel: &[usize],
+        mut terms: Option<&mut Terms>,
     ) -> f64 {
         let acts = sel
             .iter()
             .zip(bs_data)
             .map(|(idx, dat)| &dat[*idx])
             .collect::<Vec<_>>();
-        let mut score = acts.iter().fold(0.0, |b, x| {
-            b + x.0
-                + self
-                    .calc_base(&HashSet::new(), &x.2[1], &x.1)
-                    .unwrap_or(-10000.0)
-                    * self.params.FIRST_MOVE_BONUS
+        let mut score = acts.iter().enumerate().fold(0.0, |b, (i, x)| {
+            let first = self
+                .calc_base(&HashSet::new(), &x.2[1], &x.1)
+                .unwrap_or(-10000.0)
+                * self.params.FIRST_MOVE_BONUS;
+            note(&mut terms, i, "path", x.0);
+            note(&mut terms, i, "first_move", first);
+            b + x.0 + first
         });
 
         let pos_data = sel
@@ -181,16 +355,15 @@ impl SocialDistance<'_> {
             };
             for (idx1, p) in poses.iter().enumerate() {
                 for (idx2, q) in poses.iter().take(idx1).enumerate() {
-                    score -=
+                    let pena =
                         self.params.SA_DIST_PENA * per_pow_dist * prev_per[idx1] * prev_per[idx2]
                             / f(p, q).max(0.5);
+                    score -= pena;
+                    note(&mut terms, idx1, "distance", -pena / 2.0);
+                    note(&mut terms, idx2, "distance", -pena / 2.0);
                 }
             }
 
-            if output {
-                println!("{}", score);
-            }
-
             for (i, pd) in pos_data.iter().enumerate() {
                 let pos = pd[j].clone();
                 // 到達確率
@@ -216,124 +389,63 @@ impl SocialDistance<'_> {
                     State::Wall(s) if s == self.side => 0,
                     _ => tile.point(),
                 };
-                // 到達できない確率だけ減らしていく
-                score -= self.params.SA_CONF_PENA * raw_score as f64 * per_pow * (1.0 - per);
+                // 到達できない確率と, 届いても相手に取られるか取り合いになる確率だけ減らしていく
+                let contest = self.contest.get(&pos).unwrap_or(&0.0);
+                let pena = self.params.SA_CONF_PENA
+                    * raw_score as f64
+                    * per_pow
+                    * (1.0 - per * (1.0 - contest));
+                score -= pena;
+                note(&mut terms, i, "conflict", -pena);
             }
         }
         let last_pena = acts.iter().enumerate().fold(0.0, |b, (idx, x)| {
-            b + if prev_per[idx] < self.params.SA_LAST_SUPER_BORDER {
+            let pena = if prev_per[idx] < self.params.SA_LAST_SUPER_BORDER {
                 x.0 * (1.0 - prev_per[idx]) * self.params.SA_LAST_SUPER_PENA
             } else {
                 x.0 * (1.0 - prev_per[idx]).powf(self.params.SA_LAST_POW)
-            }
-        }) * self.params.SA_LAST_PENA;
+            } * self.params.SA_LAST_PENA;
+            note(&mut terms, idx, "last", -pena);
+            b + pena
+        });
         score -= last_pena;
-
-        if output {
-            println!("poses:");
-            for (i, p) in pos_data.iter().enumerate() {
-                println!("{:?} => {:?}", acts[i].0, p);
-            }
-            println!("per: {:?}", prev_per);
+        for (i, per) in prev_per.iter().enumerate() {
+            note(&mut terms, i, "reach", *per);
         }
         score
     }
 
-    fn calc_score_put(
+    fn simulated_annealing(
         &self,
-        bs_data: &Vec<(f64, Act, Vec<Point>)>,
-        pos_data: &Vec<(f64, Act, Vec<Point>)>,
-        sel: &Vec<usize>,
-        output: bool,
-    ) -> f64 {
-        let mut poses = sel
-            .iter()
-            .map(|i| bs_data[*i].2.clone())
-            .collect::<Vec<_>>();
-        poses.append(
-            &mut pos_data
-                .iter()
-                .map(|x| x.2.iter().skip(1).cloned().collect::<Vec<_>>())
-                .collect::<Vec<_>>(),
-        );
-        let mut scores = bs_data.iter().map(|x| x.0).collect::<Vec<_>>();
-        scores.append(&mut pos_data.iter().map(|x| x.0).collect::<Vec<_>>());
-        let n = poses.len();
-        let mut prob = vec![1.0; n];
-        let mut per_map = (0..self.field.width())
-            .map(|_| vec![1.0; self.field.height()])
-            .collect::<Vec<_>>();
-
-        for j in 0..DEPTH {
-            let per_pow = if j == 0 {
-                0.0
-            } else {
-                self.params.SA_CONF_PER.powf(j as f64)
-            };
-            for (i, pd) in poses.iter().enumerate() {
-                let pos = pd[j].usize();
-                // 到達確率
-                let per = if j != 0 && pd[j - 1] == pd[j] {
-                    1.0
-                } else {
-                    // マスが踏まれている確率を更新していく
-                    let bef_val = per_map[pos.x][pos.y];
-                    per_map[pos.x][pos.y] = bef_val * (1.0 - prob[i] * per_pow);
-                    bef_val
-                } * prob[i];
-                prob[i] = per;
-                if j == 0 && prob[i] != 1.0 {
-                    return -10000.0;
-                }
-            }
-        }
-        let res = prob
-            .iter()
-            .enumerate()
-            .fold(0.0, |b, (idx, x)| b + scores[idx] * x);
-
-        if output {
-            println!("{:?}", prob);
-            println!("answer: {}", res);
-        }
-        res
-    }
-
-    fn calc_score(&self, bs_res: &SaRes, sel: &Vec<usize>, output: bool) -> f64 {
-        match bs_res {
-            SaRes::Normal(x) => self.calc_score_normal(&x, sel, output),
-            SaRes::Put((x, y)) => self.calc_score_put(x, y, sel, output),
-        }
-    }
-
-    fn simulated_annealing(&self, n: usize, bs_res: &SaRes, siz_vec: Vec<usize>) -> Vec<usize> {
-        let put_fl = if let SaRes::Put(_) = bs_res {
-            true
-        } else {
-            false
-        };
-        let mut sel = if put_fl {
-            (0..n).collect::<Vec<_>>()
-        } else {
-            vec![0; n]
-        };
-        let mut now_score = self.calc_score(bs_res, &sel, false);
+        init: Vec<usize>,
+        bs_res: &[Vec<(f64, Act, Vec<Point>)>],
+        siz_vec: &[usize],
+        ctx: &mut SolveContext,
+    ) -> (f64, Vec<usize>) {
+        let n = init.len();
+        let mut sel = init;
+        let mut now_score = self.calc_score(bs_res, &sel, None);
         let mut answer = (now_score, sel.clone());
         let mut stack = Vec::new();
-        let start_time = Instant::now();
-        let mut rng = rand::thread_rng();
-        loop {
-            let elapsed = start_time.elapsed().as_secs_f64();
-            if elapsed >= SA_SEC {
+        // 反復回数で数えている場合は SA_ITER 回で温度を下げるので, シードだけで結果が決まる.
+        // 時計で数えている場合は持ち時間の経過に合わせて温度を下げ, 時間切れまで続ける.
+        // どちらも時間切れになればそれまでの最良を返す
+        for iter in 0.. {
+            if (ctx.budgeted() && iter >= SA_ITER) || ctx.spend(SA_STEPS) {
                 break;
             }
+            let progress = if ctx.budgeted() {
+                iter as f64 / SA_ITER as f64
+            } else {
+                ctx.progress()
+            };
 
-            if n <= 1 || rng.gen::<f32>() <= 0.8 {
-                let idx = rng.gen_range(0, n);
+            if n <= 1 || ctx.rng.gen::<f32>() <= 0.8 {
+                let idx = ctx.rng.gen_range(0, n);
                 let to = if siz_vec[idx] == 1 {
                     0
                 } else {
-                    let p = rng.gen_range(0, siz_vec[idx] - 1);
+                    let p = ctx.rng.gen_range(0, siz_vec[idx] - 1);
                     if p >= sel[idx] {
                         p + 1
                     } else {
@@ -343,15 +455,15 @@ impl SocialDistance<'_> {
                 stack.push((idx, sel[idx]));
                 sel[idx] = to;
             } else {
-                let idx1 = rng.gen_range(0, n);
+                let idx1 = ctx.rng.gen_range(0, n);
                 let mut idx2 = idx1;
                 while idx1 != idx2 {
-                    idx2 = rng.gen_range(0, n);
+                    idx2 = ctx.rng.gen_range(0, n);
                 }
                 let to1 = if siz_vec[idx1] == 1 {
                     0
                 } else {
-                    let p = rng.gen_range(0, siz_vec[idx1] - 1);
+                    let p = ctx.rng.gen_range(0, siz_vec[idx1] - 1);
                     if p >= sel[idx1] {
                         p + 1
                     } else {
@@ -361,7 +473,7 @@ impl SocialDistance<'_> {
                 let to2 = if siz_vec[idx2] == 1 {
                     0
                 } else {
-                    let p = rng.gen_range(0, siz_vec[idx2] - 1);
+                    let p = ctx.rng.gen_range(0, siz_vec[idx2] - 1);
                     if p >= sel[idx2] {
                         p + 1
                     } else {
@@ -374,13 +486,12 @@ impl SocialDistance<'_> {
                 sel[idx2] = to2;
             }
 
-            let nex_score = self.calc_score(&bs_res, &sel, false);
-            let temp = (END_TEMP - START_TEMP) * (start_time.elapsed().as_secs_f64() / SA_SEC)
-                + START_TEMP;
+            let nex_score = self.calc_score(bs_res, &sel, None);
+            let temp = (END_TEMP - START_TEMP) * progress + START_TEMP;
             let prob = ((nex_score - now_score) / temp).exp();
             // println!("{} => {}  ({})", now_score, nex_score, prob);
 
-            let updated = if prob >= rng.gen::<f64>() {
+            let updated = if prob >= ctx.rng.gen::<f64>() {
                 now_score = nex_score;
                 if now_score > answer.0 {
                     // println!("updated: {} {} {} => {:?}", temp, elapsed, now_score, sel);
@@ -397,10 +508,14 @@ impl SocialDistance<'_> {
                 }
             }
         }
-        // self.calc_score(&bs_res, &answer.1, true);
-        answer.1
+        answer
     }
-    fn move_confirm(&self, acts: &mut Vec<Act>) {
+    fn move_confirm(
+        &mut self,
+        acts: &mut Vec<Act>,
+        explanation: &mut Explanation,
+        ctx: &mut SolveContext,
+    ) {
         let check_fn = |id: usize| {
             if let Some(_) = self.field.agent(self.side, id) {
                 true
@@ -414,15 +529,15 @@ impl SocialDistance<'_> {
         let put_idxes = (0..self.field.agent_count())
             .filter(|id| !check_fn(*id))
             .collect::<Vec<_>>();
-        let mut move_pos_list = Vec::new();
         let poses = idxes
             .iter()
             .map(|id| self.field.agent(self.side, *id).unwrap())
             .collect::<Vec<_>>();
 
-        let bs_res = poses
-            .iter()
-            .map(|x| self.beam_search(vec![x.clone()], DEPTH, WIDTH))
+        let bs_res = idxes
+            .par_iter()
+            .zip(&poses)
+            .map(|(id, x)| self.beam_search(*id, vec![*x], DEPTH, WIDTH))
             .collect::<Vec<_>>();
         /*
         println!(
@@ -440,104 +555,69 @@ impl SocialDistance<'_> {
                 .collect::<Vec<_>>()
         );
          */
-        let selected_bs_res = if bs_res.is_empty() {
-            Vec::new()
-        } else {
-            let res = self.simulated_annealing(
-                bs_res.len(),
-                &SaRes::Normal(bs_res.clone()),
-                bs_res.iter().map(|x| x.len()).collect::<Vec<_>>(),
-            );
-            let mut selected_bs_res = Vec::new();
+        if !bs_res.is_empty() {
+            // 前のターンの経路を続けられる候補から焼きなましを始める
+            let init = idxes
+                .iter()
+                .zip(&bs_res)
+                .map(|(id, bs_v)| {
+                    let prev = &self.plan[*id];
+                    bs_v.iter()
+                        .position(|x| {
+                            prev.len() >= 2
+                                && x.2.iter().skip(1).zip(&prev[1..]).all(|(p, q)| p == q)
+                        })
+                        .unwrap_or(0)
+                })
+                .collect::<Vec<_>>();
+            let siz_vec = bs_res.iter().map(|x| x.len()).collect::<Vec<_>>();
+            // 独立に焼きなましを並列に行い, 最も良いものを使う (同点なら番号の小さい方).
+            // 各回の持ち時間は始まった時点から数え, コアが足りず後回しになった回は window の
+            // 締め切りまでの短い持ち時間で行う. 乱数は並列にする前に順に分けておく
+            let window = ctx.split(ctx.remaining() * SA_TIME_RATE);
+            let seeds = (0..SA_RESTARTS)
+                .map(|_| ctx.rng.gen())
+                .collect::<Vec<u64>>();
+            let (_, res) = seeds
+                .par_iter()
+                .map(|seed| {
+                    let mut child = window.child(SA_SEC, *seed);
+                    self.simulated_annealing(init.clone(), &bs_res, &siz_vec, &mut child)
+                })
+                .collect::<Vec<_>>()
+                .into_iter()
+                .fold(None, |best: Option<(f64, Vec<usize>)>, x| match best {
+                    Some(b) if b.0 >= x.0 => Some(b),
+                    _ => Some(x),
+                })
+                .unwrap();
             for (i, bs_v) in bs_res.iter().enumerate() {
-                move_pos_list.push(bs_v[res[i]].2.iter().map(|x| x.usize()).collect::<Vec<_>>());
-                selected_bs_res.push(bs_v[res[i]].clone());
+                self.plan[idxes[i]] = bs_v[res[i]].2.clone();
                 acts[idxes[i]] = bs_v[res[i]].1.clone();
             }
-            selected_bs_res
-        };
-        if put_idxes.is_empty() {
-            return;
-        }
-        // 多始点BFSでうまく管理する
-        let st = Instant::now();
-
-        let mut per = (0..self.field.width())
-            .map(|_| vec![1.0; self.field.height()])
-            .collect::<Vec<_>>();
 
-        for j in 1..=DEPTH {
-            let per_pow = self.params.PUT_CONF_POW.powf((j - 1) as f64);
-            for moves in &move_pos_list {
-                per[moves[j].x][moves[j].y] *= 1.0 - per_pow;
+            // 選んだ手の組の評価の内訳と, ビームサーチの候補を残す
+            let mut terms = vec![Vec::new(); idxes.len()];
+            let score = self.calc_score(&bs_res, &res, Some(&mut terms));
+            explanation.add_stat("score", score);
+            for (i, bs_v) in bs_res.iter().enumerate() {
+                let id = idxes[i];
+                for (score, act, path) in bs_v {
+                    explanation.add_candidate(id, act.clone(), *score, path.clone());
+                }
+                let bonus = self.region_bonus.get(&bs_v[res[i]].2[1]).unwrap_or(&0.0);
+                explanation.add_term(id, "region_bonus", *bonus);
+                for (name, value) in &terms[i] {
+                    explanation.add_term(id, name, *value);
+                }
             }
         }
-
-        let score_func = |x, y| {
-            let tile = self.field.tile(PointUsize::new(x, y));
-            match tile.state() {
-                State::Wall(side) if side != self.side => -20.0,
-                _ => tile.point() as f64 * per[x][y],
-            }
-        };
-
-        let (cand_list, border) = {
-            let mut res = (0..self.field.width()).fold(Vec::new(), |mut v, i| {
-                let mut w = (0..self.field.height()).fold(Vec::new(), |mut u, j| {
-                    let score = score_func(i, j);
-                    u.push(score);
-                    u
-                });
-                v.append(&mut w);
-                v
-            });
-            let res = {
-                let mut res_f = res.iter().map(|x| MinOrdFloat::new(*x)).collect::<Vec<_>>();
-                res_f.sort();
-                res_f.iter().map(|x| x.raw()).collect::<Vec<_>>()
-            };
-            let border = res[((res.len() as f64 * PUT_BORDER) as usize).min(res.len() - 1)];
-            (
-                (0..self.field.width()).fold(HashSet::new(), |v, i| {
-                    let mut w = (0..self.field.height()).fold(HashSet::new(), |mut u, j| {
-                        if score_func(i, j) >= border {
-                            u.insert(PointUsize::new(i, j).normal());
-                        }
-                        u
-                    });
-                    v.union(&w).cloned().collect::<HashSet<_>>()
-                }),
-                border,
-            )
-        };
-        let put_bs_res = self.beam_search(
-            cand_list.iter().cloned().collect::<Vec<_>>(),
-            DEPTH - 1,
-            PUT_WIDTH,
-        );
-        let put_res = self.simulated_annealing(
-            put_idxes.len(),
-            &SaRes::Put((put_bs_res.clone(), selected_bs_res)),
-            vec![put_bs_res.len(); put_idxes.len()],
-        );
-        for (i, idx) in put_res.iter().enumerate() {
-            let act = if let Act::MoveAct(p) = put_bs_res[*idx].1 {
-                Act::PutAct(p)
-            } else {
-                Act::StayAct
-            };
-            acts[put_idxes[i]] = act;
+        if put_idxes.is_empty() {
+            return;
+        }
+        for (id, pos) in placement::plan(self.side, self.field, &mut ctx.rng) {
+            acts[id] = Act::PutAct(pos);
         }
-        /*
-        println!(
-            "cand:{}, {} * {}",
-            cand_list.len(),
-            self.field.width(),
-            self.field.height()
-        );
-        println!("border: {}", border);
-        println!("time: {} [msec]", st.elapsed().as_millis());
-         */
     }
     fn reduce_cand(
         &self,
@@ -636,6 +716,7 @@ impl SocialDistance<'_> {
 
     fn beam_search(
         &self,
+        id: usize,
         start_poses: Vec<Point>,
         max_depth: usize,
         width: usize,
@@ -662,47 +743,11 @@ impl SocialDistance<'_> {
             );
             cand[t] = bef.clone();
             for now_state in bef {
-                let neighbors = base::make_neighbors(now_state.pos, self.field);
-                for nex in neighbors {
-                    let tile = self.field.tile(nex.usize());
-                    if let Some((nex_state, nex_turn)) = match tile.state() {
-                        State::Wall(side_) if self.side != side_ => {
-                            let act = Act::RemoveAct(nex);
-                            if let Some(point) = self.calc_base(&now_state.used, &nex, &act) {
-                                if t == max_depth - 1 {
-                                    Some((
-                                        now_state.from(nex, act, point * pow(self.params.PER, t)),
-                                        t + 1,
-                                    ))
-                                } else {
-                                    Some((
-                                        now_state.from(
-                                            nex,
-                                            act,
-                                            point
-                                                * (1.0 + self.params.PER)
-                                                * pow(self.params.PER, t),
-                                        ),
-                                        t + 2,
-                                    ))
-                                }
-                            } else {
-                                None
-                            }
-                        }
-                        _ => {
-                            let act = Act::MoveAct(nex);
-                            if let Some(point) = self.calc_base(&now_state.used, &nex, &act) {
-                                Some((
-                                    now_state.from(nex, act, point * pow(self.params.PER, t)),
-                                    t + 1,
-                                ))
-                            } else {
-                                None
-                            }
-                        }
-                    } {
-                        cand[nex_turn].push(nex_state);
+                for step in pathfind::steps(self.side, self.field, now_state.pos) {
+                    if let Some(point) = self.calc_base(&now_state.used, &step.to, &step.act) {
+                        let point = self.roles.adjust(id, now_state.pos, step.to, point);
+                        let (add, nex_turn) = step.gain(point, t, max_depth, self.params.PER);
+                        cand[nex_turn].push(now_state.from(step.to, step.act, add));
                     }
                 }
             }
@@ -726,15 +771,26 @@ impl SocialDistance<'_> {
         res
     }
     fn calc_base(&self, used: &HashSet<Point>, nex_pos: &Point, act: &Act) -> Option<f64> {
+        let bonus = match act {
+            Act::MoveAct(_) | Act::PutAct(_) => *self.region_bonus.get(nex_pos).unwrap_or(&0.0),
+            Act::RemoveAct(pos)
+                if self.field.tile(pos.usize()).state() == State::Wall(!self.side) =>
+            {
+                *self.region_bonus.get(pos).unwrap_or(&0.0)
+            }
+            _ => 0.0,
+        };
         match base::point(self.side, act.clone(), self.field) {
             Some(point) => Some(if used.contains(nex_pos) {
                 0.0
             } else {
-                point as f64
+                (point as f64 + bonus)
                     * if self.agent_set.contains(nex_pos) {
                         self.params.AG_CONF_PER
                     } else {
-                        1.0
+                        // 相手が来そうなマスも, その確率に応じて AG_CONF_PER に近づける
+                        let conflict = self.conflict.get(nex_pos).unwrap_or(&0.0);
+                        1.0 - conflict * (1.0 - self.params.AG_CONF_PER)
                     }
             }),
             None => None,
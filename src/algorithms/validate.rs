@@ -0,0 +1,241 @@
+use crate::field;
+use crate::simulator;
+
+use field::{Field, State};
+use simulator::Act;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Issue {
+    // ルール上適用できない手 (隣接していない, 相手の壁への移動, 壁でないマスの除去など)
+    Illegal,
+    // 自チームの他の手と対象が重なる, または自チームのエージェントがどかずに塞いでいる
+    SelfConflict,
+    // 相手のエージェントがいるマスが対象で, 相手がどかなければ適用されない (修正はしない)
+    Blocked,
+    // 相手の隣のエージェントが同じマスを対象にすると適用されない (修正はしない)
+    Contested,
+}
+
+pub struct Report {
+    // 修正した手
+    pub acts: Vec<Act>,
+    // (id, 元の手, 問題)
+    pub issues: Vec<(usize, Act, Issue)>,
+}
+
+fn target(act: &Act) -> Option<field::Point> {
+    match act {
+        Act::PutAct(pos) | Act::MoveAct(pos) | Act::RemoveAct(pos) => Some(*pos),
+        Act::StayAct => None,
+    }
+}
+
+// act_list が停止に置き換える手か
+fn legal(side: bool, id: usize, act: &Act, field: &Field) -> bool {
+    let pos = match target(act) {
+        Some(pos) if field.inside(pos) => pos,
+        Some(_) => return false,
+        None => return true,
+    };
+    let state = field.tile(pos.usize()).state();
+    match (field.agent(side, id), act) {
+        (Some(agent), Act::MoveAct(_)) => {
+            agent != pos && agent.neighbor(pos) && state != State::Wall(!side)
+        }
+        (Some(agent), Act::RemoveAct(_)) => agent != pos && agent.neighbor(pos) && state.is_wall(),
+        (None, Act::PutAct(_)) => state != State::Wall(!side),
+        _ => false,
+    }
+}
+
+// 相手の手を opp として, 自分の各手が実際に適用されるか
+fn applied(side: bool, acts: &[Act], opp: &[Act], field: &Field) -> Vec<bool> {
+    let mut joint = vec![Vec::new(); 2];
+    joint[side as usize] = acts.to_vec();
+    joint[!side as usize] = opp.to_vec();
+    let mut res = vec![false; acts.len()];
+    for (s, id, act) in simulator::act_list(&joint, field) {
+        if s == side && act == acts[id] {
+            res[id] = true;
+        }
+    }
+    res
+}
+
+// 相手が全員停止すると仮定して, 適用されない手を停止に置き換える.
+// 自チームの手どうしで対象が重なるときは id の小さい方を残す.
+// 相手のエージェントがいるマスへの手は, 相手がどけば通るので残し, その相手はいないものとして確かめ直す
+fn repair(side: bool, acts: &mut [Act], field: &Field, issues: &mut Vec<(usize, Act, Issue)>) {
+    let stay = vec![Act::StayAct; field.agent_count()];
+    let mut assumed = field.clone();
+    loop {
+        let ok = applied(side, acts, &stay, &assumed);
+        let failed = (0..acts.len())
+            .filter(|id| !ok[*id] && acts[*id] != Act::StayAct)
+            .collect::<Vec<_>>();
+        if failed.is_empty() {
+            return;
+        }
+        let mut by_target: HashMap<_, Vec<usize>> = HashMap::new();
+        for id in &failed {
+            by_target
+                .entry(target(&acts[*id]).unwrap())
+                .or_default()
+                .push(*id);
+        }
+        let mut drop = Vec::new();
+        for ids in by_target.values().filter(|ids| ids.len() >= 2) {
+            drop.extend(ids[1..].iter().cloned());
+        }
+        // 重なりを解消すれば通る手もあるので, 重なりがなくなってから残りを見る
+        if drop.is_empty() {
+            for id in failed {
+                let pos = target(&acts[id]).unwrap();
+                match (0..field.agent_count()).find(|i| assumed.agent(!side, *i) == Some(pos)) {
+                    Some(opp_id) => {
+                        issues.push((id, acts[id].clone(), Issue::Blocked));
+                        assumed.set_agent(!side, opp_id, None);
+                    }
+                    None => drop.push(id),
+                }
+            }
+        }
+        for id in drop {
+            issues.push((id, acts[id].clone(), Issue::SelfConflict));
+            acts[id] = Act::StayAct;
+        }
+    }
+}
+
+// 隣にいる相手のエージェント 1 人が同じマスを対象にしたとき, 適用されなくなる手の id
+fn contested(side: bool, acts: &[Act], field: &Field) -> Vec<usize> {
+    let mut res = Vec::new();
+    for (id, act) in acts.iter().enumerate() {
+        let pos = match target(act) {
+            Some(pos) => pos,
+            None => continue,
+        };
+        // Blocked として報告済み
+        if (0..field.agent_count()).any(|i| field.agent(!side, i) == Some(pos)) {
+            continue;
+        }
+        let rival = if field.tile(pos.usize()).state() == State::Wall(side) {
+            Act::RemoveAct(pos)
+        } else {
+            Act::MoveAct(pos)
+        };
+        let hit = (0..field.agent_count()).any(|opp_id| match field.agent(!side, opp_id) {
+            Some(opp_pos) if opp_pos != pos && opp_pos.neighbor(pos) => {
+                let mut opp = vec![Act::StayAct; field.agent_count()];
+                opp[opp_id] = rival.clone();
+                !applied(side, acts, &opp, field)[id]
+            }
+            _ => false,
+        });
+        if hit {
+            res.push(id);
+        }
+    }
+    res
+}
+
+// 送信する前に自チームの手の組を act_list で確かめ, 問題のある手を停止に置き換える.
+// 自チームの手だけで成立しない手は修正し, 相手の動き次第で適用されない手は報告だけする
+pub fn validate(side: bool, acts: &[Act], field: &Field) -> Report {
+    let mut issues = Vec::new();
+    let mut res = acts.to_vec();
+    res.resize(field.agent_count(), Act::StayAct);
+    for (id, act) in res.iter_mut().enumerate() {
+        if !legal(side, id, act, field) {
+            issues.push((id, act.clone(), Issue::Illegal));
+            *act = Act::StayAct;
+        }
+    }
+    repair(side, &mut res, field, &mut issues);
+    for id in contested(side, &res, field) {
+        issues.push((id, res[id].clone(), Issue::Contested));
+    }
+    Report { acts: res, issues }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use field::Point;
+
+    fn field(ours: &[Option<Point>], theirs: &[Option<Point>]) -> Field {
+        let mut field = Field::from_points(&[&[1, 1, 1], &[1, 1, 1], &[1, 1, 1]], ours.len());
+        for (id, pos) in ours.iter().enumerate() {
+            field.set_agent(false, id, *pos);
+        }
+        for (id, pos) in theirs.iter().enumerate() {
+            field.set_agent(true, id, *pos);
+        }
+        field
+    }
+
+    #[test]
+    fn opponent_stays() {
+        let field = field(&[Some(Point::new(0, 0))], &[Some(Point::new(1, 0))]);
+        // 相手がどけば通るので, 手は残して報告だけする
+        let act = Act::MoveAct(Point::new(1, 0));
+        let report = validate(false, std::slice::from_ref(&act), &field);
+        assert_eq!(report.acts, vec![act.clone()]);
+        assert_eq!(report.issues, vec![(0, act, Issue::Blocked)]);
+    }
+
+    #[test]
+    fn opponent_contests() {
+        let field = field(&[Some(Point::new(0, 0))], &[Some(Point::new(2, 0))]);
+        let act = Act::MoveAct(Point::new(1, 0));
+        let report = validate(false, std::slice::from_ref(&act), &field);
+        assert_eq!(report.acts, vec![act.clone()]);
+        assert_eq!(report.issues, vec![(0, act, Issue::Contested)]);
+    }
+
+    #[test]
+    fn put_onto_occupied_tile() {
+        let field = field(&[Some(Point::new(1, 1)), None], &[None, None]);
+        let put = Act::PutAct(Point::new(1, 1));
+        let report = validate(false, &[Act::StayAct, put.clone()], &field);
+        assert_eq!(report.acts, vec![Act::StayAct, Act::StayAct]);
+        assert_eq!(report.issues, vec![(1, put, Issue::SelfConflict)]);
+    }
+
+    #[test]
+    fn repair_terminates() {
+        // 入れ替わりと, 相手のいるマスの取り合いが同時に起きても止まる
+        let field = field(
+            &[
+                Some(Point::new(0, 0)),
+                Some(Point::new(1, 0)),
+                Some(Point::new(0, 2)),
+                Some(Point::new(2, 2)),
+            ],
+            &[Some(Point::new(1, 2)), None, None, None],
+        );
+        let acts = vec![
+            Act::MoveAct(Point::new(1, 0)),
+            Act::MoveAct(Point::new(0, 0)),
+            Act::MoveAct(Point::new(1, 2)),
+            Act::MoveAct(Point::new(1, 2)),
+        ];
+        let report = validate(false, &acts, &field);
+        assert_eq!(
+            report.acts,
+            vec![Act::StayAct, Act::StayAct, acts[2].clone(), Act::StayAct]
+        );
+        let mut issues = report.issues.iter().map(|x| (x.0, x.2)).collect::<Vec<_>>();
+        issues.sort_by_key(|x| x.0);
+        assert_eq!(
+            issues,
+            vec![
+                (0, Issue::SelfConflict),
+                (1, Issue::SelfConflict),
+                (2, Issue::Blocked),
+                (3, Issue::SelfConflict),
+            ]
+        );
+    }
+}
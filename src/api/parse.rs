@@ -2,8 +2,9 @@ use crate::field;
 
 use druid::Data;
 use field::Field;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::BTreeMap;
 use std::fs::File;
 use try_from::TryFrom;
 
@@ -40,6 +41,18 @@ pub struct Config {
     pub token: String,
     pub url: String,
     pub visualizer: bool,
+    #[serde(default = "default_solver")]
+    pub solver: String,
+    #[serde(default = "default_turn_sec")]
+    pub turn_sec: f64,
+}
+
+fn default_solver() -> String {
+    "social_distance".to_string()
+}
+
+fn default_turn_sec() -> f64 {
+    0.2
 }
 
 macro_rules! err_ret {
@@ -202,7 +215,7 @@ pub fn read_config_json(path: &str) -> Config {
     res
 }
 
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Params {
     pub PER: f64,
     pub FIRST_MOVE_BONUS: f64,
@@ -210,7 +223,10 @@ pub struct Params {
     pub AG_CONF_PER: f64,
     pub REGION_PER: f64,
     pub REGION_POW: f64,
-    pub PUT_CONF_POW: f64,
+    pub ATTACK_PER: f64,
+    pub ATTACK_POW: f64,
+    pub DEFENSE_PER: f64,
+    pub DEFENSE_POW: f64,
 
     pub SA_LAST_PENA: f64,
     pub SA_LAST_POW: f64,
@@ -234,9 +250,12 @@ impl Params {
             FIRST_MOVE_BONUS: 1.75,
 
             AG_CONF_PER: 0.58,
-            REGION_PER: 0.00,
+            REGION_PER: 1.00,
             REGION_POW: 0.85,
-            PUT_CONF_POW: 0.75,
+            ATTACK_PER: 0.50,
+            ATTACK_POW: 0.85,
+            DEFENSE_PER: 0.10,
+            DEFENSE_POW: 0.85,
 
             SA_LAST_PENA: 0.5,
             SA_LAST_POW: 4.8,
@@ -260,7 +279,6 @@ impl Params {
             AG_CONF_PER: 0.58,
             REGION_PER: 0.50,
             REGION_POW: 0.85,
-            PUT_CONF_POW: 0.75,
 
             SA_LAST_PENA: 0.5,
             SA_LAST_POW: 4.8,
@@ -285,7 +303,6 @@ impl Params {
             AG_CONF_PER: 0.3,
             REGION_PER: 1.0,
             REGION_POW: 0.9,
-            PUT_CONF_POW: 0.7,
 
             SA_LAST_PENA: 0.3,
             SA_LAST_POW: 3.5,
@@ -310,3 +327,102 @@ pub fn read_params(path: &str) -> Params {
     let res = serde_json::from_reader(fp).expect("params parse error");
     res
 }
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Schedule {
+    Const(f64),
+    Phase { phases: Vec<f64> },
+    Linear { points: Vec<(f64, f64)> },
+}
+
+impl Schedule {
+    pub fn value(&self, progress: f64) -> f64 {
+        match self {
+            Schedule::Const(val) => *val,
+            Schedule::Phase { phases } => {
+                let idx = (progress * phases.len() as f64) as usize;
+                phases[idx.min(phases.len() - 1)]
+            }
+            Schedule::Linear { points } => {
+                let mut points = points.clone();
+                points.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+                let first = points[0];
+                let last = points[points.len() - 1];
+                if progress <= first.0 {
+                    return first.1;
+                }
+                if progress >= last.0 {
+                    return last.1;
+                }
+                let idx = points.iter().position(|p| progress < p.0).unwrap();
+                let (l, r) = (points[idx - 1], points[idx]);
+                l.1 + (r.1 - l.1) * (progress - l.0) / (r.0 - l.0)
+            }
+        }
+    }
+}
+
+// 削除したパラメータ. 古いパラメータファイルに残っていても警告を出して読み飛ばす
+// (PUT_CONF_POW は social_distance の定数になったので, ファイルからは消してよい)
+const LEGACY_PARAMS: [&str; 1] = ["PUT_CONF_POW"];
+
+// 各パラメータを now_turn / final_turn の関数として与える (書かれていないものは Params::default のまま)
+#[derive(Clone, Deserialize, Serialize)]
+pub struct ParamSchedule(BTreeMap<String, Schedule>);
+
+impl ParamSchedule {
+    pub fn constant(params: &Params) -> ParamSchedule {
+        let map = serde_json::to_value(params).expect("params serialize error");
+        ParamSchedule(serde_json::from_value(map).expect("params serialize error"))
+    }
+    pub fn resolve(&self, field: &Field) -> Params {
+        let progress = if field.final_turn() == 0 {
+            0.0
+        } else {
+            field.now_turn() as f64 / field.final_turn() as f64
+        };
+        let mut map = serde_json::to_value(Params::default()).expect("params serialize error");
+        for (k, schedule) in &self.0 {
+            map[k] = Value::from(schedule.value(progress));
+        }
+        serde_json::from_value(map).expect("params parse error")
+    }
+    fn validate(&self) -> Result<(), String> {
+        let default = serde_json::to_value(Params::default()).map_err(|e| e.to_string())?;
+        for (k, schedule) in &self.0 {
+            if default.get(k).is_none() {
+                return Err(format!("unknown param {}", k));
+            }
+            match schedule {
+                Schedule::Phase { phases } if phases.is_empty() => {
+                    return Err(format!("{}: phases is empty", k))
+                }
+                Schedule::Linear { points } if points.is_empty() => {
+                    return Err(format!("{}: points is empty", k))
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn param_schedule_from_value(val: Value) -> Result<ParamSchedule, String> {
+    let mut schedule: ParamSchedule = serde_json::from_value(val).map_err(|e| e.to_string())?;
+    schedule.0.retain(|k, _| {
+        let legacy = LEGACY_PARAMS.contains(&k.as_str());
+        if legacy {
+            eprintln!("WARN: param {} was removed and is ignored", k);
+        }
+        !legacy
+    });
+    schedule.validate()?;
+    Ok(schedule)
+}
+
+pub fn read_param_schedule(path: &str) -> Result<ParamSchedule, String> {
+    let fp = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+    let val = serde_json::from_reader(fp).map_err(|e| format!("{}: {}", path, e))?;
+    param_schedule_from_value(val).map_err(|e| format!("{}: {}", path, e))
+}
@@ -1,12 +1,17 @@
 use crate::api::parse::TeamData;
 use druid::Data;
 use rand;
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::collections::VecDeque;
 use std::ops::Add;
 
-#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 pub struct Point {
     pub x: i8,
     pub y: i8,
@@ -72,7 +77,7 @@ impl PointUsize {
     }
 }
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Score {
     tile: i16,
     region: i16,
@@ -90,14 +95,14 @@ impl Score {
     }
 }
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub enum State {
     Neutral,
     Position(bool),
     Wall(bool),
 }
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Tile {
     state: State,
     point: i8,
@@ -129,7 +134,7 @@ impl Tile {
     }
 }
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct Field {
     now_turn: u8,
     final_turn: u8,
@@ -150,7 +155,27 @@ impl Field {
         height_: Option<usize>,
         agent_count_: Option<usize>,
     ) -> Field {
-        let mut rng = rand::thread_rng();
+        Field::generate(width_, height_, agent_count_, &mut rand::thread_rng())
+    }
+    pub fn with_seed(
+        width_: Option<usize>,
+        height_: Option<usize>,
+        agent_count_: Option<usize>,
+        seed: u64,
+    ) -> Field {
+        Field::generate(
+            width_,
+            height_,
+            agent_count_,
+            &mut StdRng::seed_from_u64(seed),
+        )
+    }
+    fn generate<R: Rng>(
+        width_: Option<usize>,
+        height_: Option<usize>,
+        agent_count_: Option<usize>,
+        rng: &mut R,
+    ) -> Field {
         let width = if let Some(num) = width_ {
             num
         } else {
@@ -262,6 +287,27 @@ impl Field {
     pub fn set_agent(&mut self, side: bool, id: usize, pos: Option<Point>) {
         self.agents[side as usize][id] = pos
     }
+    // 記録ファイルに保存されるので, ツールチェインに依存しない FNV-1a で盤面を要約する
+    pub fn fingerprint(&self) -> u64 {
+        let mut bytes = vec![self.now_turn];
+        for tile in self.tiles.iter().flatten() {
+            bytes.push(match tile.state {
+                State::Neutral => 0,
+                State::Position(side) => 1 + side as u8,
+                State::Wall(side) => 3 + side as u8,
+            });
+            bytes.push(tile.point as u8);
+        }
+        for pos in self.agents.iter().flatten() {
+            match pos {
+                Some(pos) => bytes.extend(&[1, pos.x as u8, pos.y as u8]),
+                None => bytes.push(0),
+            }
+        }
+        bytes.iter().fold(FNV_OFFSET, |hash, b| {
+            (hash ^ *b as u64).wrapping_mul(FNV_PRIME)
+        })
+    }
     pub fn inside(&self, pos: Point) -> bool {
         let u_pos = pos.usize();
         0 <= pos.x.min(pos.y) && u_pos.x < self.width() && u_pos.y < self.height()
@@ -373,3 +419,27 @@ impl Field {
             .collect()
     }
 }
+
+#[cfg(test)]
+impl Field {
+    // テスト用. points[y][x] の点で壁も陣地もない盤面
+    pub fn from_points(points: &[&[i8]], agent_count: usize) -> Field {
+        Field {
+            now_turn: 0,
+            final_turn: 50,
+            tiles: (0..points[0].len())
+                .map(|x| {
+                    points
+                        .iter()
+                        .map(|row| Tile {
+                            state: State::Neutral,
+                            point: row[x],
+                        })
+                        .collect()
+                })
+                .collect(),
+            agents: vec![vec![None; agent_count]; 2],
+            scores: vec![Score { tile: 0, region: 0 }; 2],
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use crate::field;
+
+use field::{Field, Score};
+use serde::Serialize;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize)]
+pub enum Outcome {
+    Win,
+    Draw,
+    Loss,
+}
+
+// 総得点 -> タイルポイントの順に比較し, それでも並んだら引き分け (公式ルールでは抽選)
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize)]
+pub enum Criterion {
+    TotalScore,
+    TilePoint,
+    Undecided,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub struct MatchResult {
+    winner: Option<bool>,
+    criterion: Criterion,
+    scores: [Score; 2],
+}
+
+impl MatchResult {
+    pub fn from_field(field: &Field) -> MatchResult {
+        let scores = [field.score(false), field.score(true)];
+        let chain: [(Criterion, fn(&Score) -> i16); 2] = [
+            (Criterion::TotalScore, Score::sum),
+            (Criterion::TilePoint, Score::tile),
+        ];
+        for (criterion, key) in chain.iter() {
+            let (a, b) = (key(&scores[0]), key(&scores[1]));
+            if a != b {
+                return MatchResult {
+                    winner: Some(a < b),
+                    criterion: *criterion,
+                    scores,
+                };
+            }
+        }
+        MatchResult {
+            winner: None,
+            criterion: Criterion::Undecided,
+            scores,
+        }
+    }
+    pub fn winner(&self) -> Option<bool> {
+        self.winner
+    }
+    pub fn criterion(&self) -> Criterion {
+        self.criterion
+    }
+    pub fn score(&self, side: bool) -> Score {
+        self.scores[side as usize]
+    }
+    pub fn diff(&self, side: bool) -> i16 {
+        self.scores[side as usize].sum() - self.scores[!side as usize].sum()
+    }
+    pub fn outcome(&self, side: bool) -> Outcome {
+        match self.winner {
+            Some(winner) if winner == side => Outcome::Win,
+            Some(_) => Outcome::Loss,
+            None => Outcome::Draw,
+        }
+    }
+}
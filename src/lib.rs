@@ -1,7 +1,10 @@
 pub mod field;
+pub mod judge;
+pub mod record;
 pub mod simulator;
 pub mod visualizer;
 
 pub mod algorithms;
 pub mod api;
 pub mod manager;
+pub mod tuner;
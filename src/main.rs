@@ -1,19 +1,87 @@
 use druid::AppLauncher;
 use druid::WindowDesc;
 
+use procon31_comp::algorithms::registry;
 use procon31_comp::api::parse;
 use procon31_comp::field;
+use procon31_comp::judge;
 use procon31_comp::manager;
+use procon31_comp::record;
 use procon31_comp::simulator;
+use procon31_comp::tuner;
 use procon31_comp::visualizer;
 
+const SOLVER: &str = "social_distance:./data/params.json";
+const OPPONENT: &str = "social_distance";
+const RESULT: &str = "./data/result.json";
+
 fn main() {
+    let args = std::env::args().collect::<Vec<_>>();
+    let get = |idx: usize, default: &str| args.get(idx).cloned().unwrap_or(default.to_string());
+    if args.len() >= 2 && args[1] == "tune" {
+        let res = get(4, "cmaes").parse().and_then(|method| {
+            let num_trials = get(5, "500")
+                .parse()
+                .map_err(|e| format!("num_trials: {}", e))?;
+            tuner::run(
+                &get(2, "./data/params_default.json"),
+                &get(3, "./data/trials.jsonl"),
+                method,
+                num_trials,
+            )
+        });
+        if let Err(e) = res {
+            println!("ERROR: {}", e);
+        }
+        return;
+    }
+    if args.len() >= 3 && args[1] == "replay" {
+        let record = match record::GameRecord::load(&args[2]) {
+            Ok(record) => record,
+            Err(e) => {
+                println!("ERROR: {}", e);
+                return;
+            }
+        };
+        let solver = get(3, SOLVER);
+        let fallback = get(4, OPPONENT);
+        for spec in &[&solver, &fallback] {
+            if let Err(e) = registry::make_solver(spec, !record.side) {
+                println!("ERROR: {}", e);
+                return;
+            }
+        }
+        let ours = |side| registry::make_solver(&solver, side).expect("solver parse error");
+        let theirs = |side| registry::make_solver(&fallback, side).expect("solver parse error");
+        let before = judge::MatchResult::from_field(&record.final_field());
+        let after = manager::replay(&record, &ours, &theirs);
+        println!(
+            "recorded: {} - {} ({:?})",
+            before.score(record.side).sum(),
+            before.score(!record.side).sum(),
+            before.outcome(record.side)
+        );
+        println!(
+            "replayed: {} - {} ({:?})",
+            after.score[0], after.score[1], after.outcome
+        );
+        return;
+    }
+    if args.len() >= 2 && args[1] == "simulate" {
+        manager::simulate(&get(2, SOLVER), &get(3, OPPONENT), &get(4, RESULT));
+        return;
+    }
+
     let mut data = visualizer::AppData {
         simulator: simulator::Simulator::new(field::Field::new(None, None, None)),
         config: parse::read_config_json("./data/config.json"),
         match_data: None,
         team_data: None,
         team_data_idx: 0,
+        record: None,
+        solver: None,
+        solver_turn: None,
+        show_influence: false,
     };
     data.simulator.inc_turn();
 
@@ -24,7 +92,6 @@ fn main() {
             .launch(data)
             .expect("launch failed")
     } else {
-        let params = parse::read_params("./data/params.json");
-        manager::simulate(params);
+        manager::simulate(SOLVER, OPPONENT, RESULT);
     }
 }
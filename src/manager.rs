@@ -1,44 +1,198 @@
-use crate::algorithms::{SimpleDp, SocialDistance, Solver};
-use crate::api::parse::Params;
+use crate::algorithms::{registry, Ghost, SolveContext, Solver};
+use crate::api::parse::ParamSchedule;
 use crate::field;
+use crate::judge::{Criterion, MatchResult, Outcome};
+use crate::record::{GameRecord, TurnRecord};
 use crate::simulator;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+use std::fs::File;
 
 const CNT: usize = 6;
+const RECORD_DIR: &str = "./data/records";
+const TURN_SEC: f64 = 0.15;
+// シード付きの試合では時計ではなく反復の重さで持ち時間を数える (1 単位がおよそ 1 マイクロ秒)
+const TURN_STEPS: usize = 150_000;
 
-pub fn simulate(params: Params) {
-    let mut diff_sum = 0;
-    for i in 0..CNT {
-        let mo = i % 2;
-        let mut sim = simulator::Simulator::new(field::Field::new(None, None, None));
+#[derive(Serialize)]
+pub struct GameReport {
+    pub game: usize,
+    pub seed: u64,
+    pub side: bool,
+    pub width: usize,
+    pub height: usize,
+    pub agent_count: usize,
+    pub score: [i16; 2],
+    pub tile: [i16; 2],
+    pub region: [i16; 2],
+    pub diff: i16,
+    pub outcome: Outcome,
+    pub criterion: Criterion,
+    #[serde(skip)]
+    pub record: GameRecord,
+}
 
-        while sim.get_field().now_turn() != sim.get_field().final_turn() {
-            let mut solver_1 = SocialDistance::new(false, sim.get_field());
-            let mut solver_2 = SocialDistance::new(true, sim.get_field());
+#[derive(Serialize)]
+pub struct SimulateReport {
+    pub solver: String,
+    pub opponent: String,
+    pub params: Option<ParamSchedule>,
+    pub games: Vec<GameReport>,
+    pub win: usize,
+    pub draw: usize,
+    pub loss: usize,
+    pub diff_sum: i16,
+}
 
-            if mo == 1 {
-                solver_2.set_params(params.clone());
-            } else {
-                solver_1.set_params(params.clone());
-            }
+pub type Factory<'a> = dyn Fn(bool) -> Box<dyn Solver> + 'a;
 
-            let act_1 = solver_1.solve();
-            let act_2 = solver_2.solve();
+impl SimulateReport {
+    pub fn new(
+        solver: &str,
+        opponent: &str,
+        params: Option<ParamSchedule>,
+        games: Vec<GameReport>,
+    ) -> SimulateReport {
+        let count = |outcome| games.iter().filter(|g| g.outcome == outcome).count();
+        SimulateReport {
+            solver: solver.to_string(),
+            opponent: opponent.to_string(),
+            params,
+            win: count(Outcome::Win),
+            draw: count(Outcome::Draw),
+            loss: count(Outcome::Loss),
+            diff_sum: games.iter().map(|g| g.diff).sum(),
+            games,
+        }
+    }
+}
 
-            for (i, x) in act_1.iter().enumerate() {
-                sim.set_act(false, i, x.clone());
-            }
+fn context(seed: u64, history: &[TurnRecord], fixed: bool) -> SolveContext<'_> {
+    let ctx = SolveContext::new(TURN_SEC, seed, history);
+    if fixed {
+        ctx.with_budget(TURN_STEPS)
+    } else {
+        ctx
+    }
+}
+
+fn run(
+    game: usize,
+    seed: Option<u64>,
+    side: bool,
+    field: field::Field,
+    mut ours: Box<dyn Solver>,
+    mut opponent: Box<dyn Solver>,
+) -> GameReport {
+    let mut sim = simulator::Simulator::new(field);
+    let mut record = GameRecord::new(side, sim.get_field(), seed);
+    // シードがあれば各ターンの乱数と持ち時間の反復回数も固定して再現できるようにする
+    let mut rng = match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
 
-            for (i, x) in act_2.iter().enumerate() {
-                sim.set_act(true, i, x.clone());
+    while sim.get_field().now_turn() != sim.get_field().final_turn() {
+        let mut acts = vec![Vec::new(); 2];
+        {
+            let mut ctx = context(rng.gen(), &record.turns, seed.is_some());
+            acts[side as usize] = ours.solve(sim.get_field(), &mut ctx);
+            let mut ctx = context(rng.gen(), &record.turns, seed.is_some());
+            acts[!side as usize] = opponent.solve(sim.get_field(), &mut ctx);
+        }
+        record.push(sim.get_field(), acts.clone());
+
+        for s in [false, true] {
+            for (i, x) in acts[s as usize].iter().enumerate() {
+                sim.set_act(s, i, x.clone());
             }
-            sim.change_turn();
         }
+        sim.change_turn();
+        ours.update(sim.get_field(), sim.last_resolved());
+        opponent.update(sim.get_field(), sim.last_resolved());
+    }
+
+    let field = sim.get_field();
+    let result = MatchResult::from_field(field);
+    GameReport {
+        game,
+        seed: seed.unwrap_or(0),
+        side,
+        width: field.width(),
+        height: field.height(),
+        agent_count: field.agent_count(),
+        score: [result.score(side).sum(), result.score(!side).sum()],
+        tile: [result.score(side).tile(), result.score(!side).tile()],
+        region: [result.score(side).region(), result.score(!side).region()],
+        diff: result.diff(side),
+        outcome: result.outcome(side),
+        criterion: result.criterion(),
+        record,
+    }
+}
+
+pub fn play(game: usize, seed: u64, side: bool, ours: &Factory, opponent: &Factory) -> GameReport {
+    let field = field::Field::with_seed(None, None, None, seed);
+    run(game, Some(seed), side, field, ours(side), opponent(!side))
+}
+
+// 記録の相手 (!record.side) を再生しつつ, ours で record.side を打ち直す
+pub fn replay(record: &GameRecord, ours: &Factory, fallback: &Factory) -> GameReport {
+    let side = record.side;
+    let ghost = Ghost::new(!side, record.clone(), fallback(!side));
+    run(
+        0,
+        record.seed,
+        side,
+        record.field.clone(),
+        ours(side),
+        Box::new(ghost),
+    )
+}
+
+pub fn evaluate(ours: &Factory, opponent: &Factory, seeds: &[u64]) -> Vec<GameReport> {
+    let mut games = Vec::new();
+    for (i, seed) in seeds.iter().enumerate() {
+        let game = play(i, *seed, i % 2 == 1, ours, opponent);
         println!(
-            "{} - {}",
-            sim.get_field().score(mo == 1).sum(),
-            sim.get_field().score(mo == 0).sum()
+            "{} - {} ({:?} by {:?})",
+            game.score[0], game.score[1], game.outcome, game.criterion
         );
-        diff_sum += sim.get_field().score(mo == 1).sum() - sim.get_field().score(mo == 0).sum();
+        games.push(game);
+    }
+    games
+}
+
+pub fn simulate(solver: &str, opponent: &str, output: &str) {
+    // 指定の誤りは試合を始める前に報告する
+    let params = match registry::params(solver).and_then(|params| {
+        registry::make_solver(solver, false)?;
+        registry::make_solver(opponent, true)?;
+        Ok(params)
+    }) {
+        Ok(params) => params,
+        Err(e) => {
+            println!("ERROR: {}", e);
+            return;
+        }
+    };
+    let ours = |side| registry::make_solver(solver, side).expect("solver parse error");
+    let theirs = |side| registry::make_solver(opponent, side).expect("solver parse error");
+    let mut rng = rand::thread_rng();
+    let seeds = (0..CNT).map(|_| rng.gen::<u64>()).collect::<Vec<_>>();
+    let games = evaluate(&ours, &theirs, &seeds);
+    let report = SimulateReport::new(solver, opponent, params, games);
+    for game in &report.games {
+        let path = format!("{}/{}.json", RECORD_DIR, game.seed);
+        if let Err(e) = game.record.save(&path) {
+            println!("ERROR: {}", e);
+        }
     }
-    println!("{}", diff_sum);
+    println!(
+        "{}W {}D {}L, diff: {}",
+        report.win, report.draw, report.loss, report.diff_sum
+    );
+    let fp = File::create(output).expect("couldn't create result file");
+    serde_json::to_writer_pretty(fp, &report).expect("result write error");
 }
@@ -0,0 +1,153 @@
+use crate::field;
+use crate::simulator;
+
+use field::Field;
+use serde::{Deserialize, Serialize};
+use simulator::{Act, Simulator};
+use std::fs::{self, File};
+use std::path::Path;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TurnRecord {
+    pub turn: u8,
+    pub fingerprint: u64,
+    pub acts: Vec<Vec<Act>>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GameRecord {
+    pub side: bool,
+    pub seed: Option<u64>,
+    pub field: Field,
+    pub turns: Vec<TurnRecord>,
+    #[serde(skip)]
+    last: Option<Field>,
+}
+
+impl GameRecord {
+    pub fn new(side: bool, field: &Field, seed: Option<u64>) -> GameRecord {
+        GameRecord {
+            side,
+            seed,
+            field: field.clone(),
+            turns: Vec::new(),
+            last: Some(field.clone()),
+        }
+    }
+    pub fn load(path: &str) -> Result<GameRecord, String> {
+        let fp = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+        let mut record: GameRecord =
+            serde_json::from_reader(fp).map_err(|e| format!("{}: {}", path, e))?;
+        record.last = Some(record.final_field());
+        Ok(record)
+    }
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        if let Some(dir) = Path::new(path).parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        let fp = File::create(path).map_err(|e| e.to_string())?;
+        serde_json::to_writer(fp, self).map_err(|e| e.to_string())
+    }
+    // 手を打つ直前の盤面と, そのターンに提出された手を記録する
+    pub fn push(&mut self, field: &Field, acts: Vec<Vec<Act>>) {
+        self.turns.push(TurnRecord {
+            turn: field.now_turn(),
+            fingerprint: field.fingerprint(),
+            acts,
+        });
+    }
+    // サーバーからは盤面しか取れないので, 前ターンとの差分から行動を推定して記録する
+    pub fn push_field(&mut self, next: &Field) {
+        if let Some(prev) = self.last.take() {
+            if prev.now_turn() < next.now_turn() {
+                let acts = infer_acts(&prev, next);
+                self.push(&prev, acts);
+            }
+        }
+        self.last = Some(next.clone());
+    }
+    pub fn acts(&self, side: bool, field: &Field) -> Option<Vec<Act>> {
+        self.turns
+            .iter()
+            .find(|t| t.turn == field.now_turn())
+            .filter(|t| t.fingerprint == field.fingerprint())
+            .map(|t| t.acts[side as usize].clone())
+    }
+    pub fn final_field(&self) -> Field {
+        let mut sim = Simulator::new(self.field.clone());
+        for turn in &self.turns {
+            for side in [false, true] {
+                for (id, act) in turn.acts[side as usize].iter().enumerate() {
+                    sim.set_act(side, id, act.clone());
+                }
+            }
+            sim.change_turn();
+        }
+        sim.get_field().clone()
+    }
+}
+
+// 除去された壁は隣にいて動かなかったエージェントの誰かが除去したものとして, 二部マッチングで割り当てる
+pub fn infer_acts(prev: &Field, next: &Field) -> Vec<Vec<Act>> {
+    let mut acts = vec![vec![Act::StayAct; prev.agent_count()]; 2];
+    let mut stayed = Vec::new();
+    for side in [false, true] {
+        for (id, act) in acts[side as usize].iter_mut().enumerate() {
+            *act = match (prev.agent(side, id), next.agent(side, id)) {
+                (None, Some(p)) => Act::PutAct(p),
+                (Some(p), Some(q)) if p != q => Act::MoveAct(q),
+                (Some(p), _) => {
+                    stayed.push((side, id, p));
+                    Act::StayAct
+                }
+                _ => Act::StayAct,
+            };
+        }
+    }
+    let mut removed = Vec::new();
+    for x in 0..prev.width() {
+        for y in 0..prev.height() {
+            let pos = field::PointUsize::new(x, y);
+            if prev.tile(pos).state().is_wall() && !next.tile(pos).state().is_wall() {
+                removed.push(pos.normal());
+            }
+        }
+    }
+
+    let mut matched: Vec<Option<usize>> = vec![None; removed.len()];
+    for agent_idx in 0..stayed.len() {
+        let mut visited = vec![false; removed.len()];
+        augment(agent_idx, &stayed, &removed, &mut matched, &mut visited);
+    }
+    for (tile_idx, agent_idx) in matched.iter().enumerate() {
+        if let Some(agent_idx) = agent_idx {
+            let (side, id, _) = stayed[*agent_idx];
+            acts[side as usize][id] = Act::RemoveAct(removed[tile_idx]);
+        }
+    }
+    acts
+}
+
+fn augment(
+    agent_idx: usize,
+    stayed: &[(bool, usize, field::Point)],
+    removed: &[field::Point],
+    matched: &mut [Option<usize>],
+    visited: &mut [bool],
+) -> bool {
+    for tile_idx in 0..removed.len() {
+        if visited[tile_idx] || !stayed[agent_idx].2.neighbor(removed[tile_idx]) {
+            continue;
+        }
+        visited[tile_idx] = true;
+        let ok = match matched[tile_idx] {
+            None => true,
+            Some(other) => augment(other, stayed, removed, matched, visited),
+        };
+        if ok {
+            matched[tile_idx] = Some(agent_idx);
+            return true;
+        }
+    }
+    false
+}
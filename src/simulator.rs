@@ -1,8 +1,9 @@
 use crate::field;
 use druid::Data;
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet, VecDeque};
 
-#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Serialize, Deserialize)]
 pub enum Act {
     StayAct,
     PutAct(field::Point),
@@ -30,6 +31,7 @@ pub struct Simulator {
     field: field::Field,
     acts: Vec<Vec<Act>>,
     act_flag: [bool; 2],
+    last_resolved: Vec<Vec<Act>>,
 }
 
 impl Data for Simulator {
@@ -44,6 +46,7 @@ impl Simulator {
             field: field.clone(),
             acts: vec![vec![Act::StayAct; field.agent_count()]; 2],
             act_flag: [false, false],
+            last_resolved: vec![vec![Act::StayAct; field.agent_count()]; 2],
         }
     }
     pub fn get_field(&self) -> &field::Field {
@@ -68,6 +71,10 @@ impl Simulator {
         self.acts[side as usize][id] = act;
         self.act_flag[side as usize] = true;
     }
+    // 前のターンに実際に適用された手 (競合などで無効になったものは StayAct)
+    pub fn last_resolved(&self) -> &Vec<Vec<Act>> {
+        &self.last_resolved
+    }
     pub fn inc_turn(&mut self) {
         self.field.update_turn();
     }
@@ -80,7 +87,9 @@ impl Simulator {
         }
 
         let act_vec = act_list(&self.acts, &self.field);
+        self.last_resolved = vec![vec![Act::StayAct; self.field.agent_count()]; 2];
         for (side, id, act) in act_vec {
+            self.last_resolved[side as usize][id] = act.clone();
             match act {
                 Act::PutAct(nex_pos) => self.field.set_agent(side, id, Some(nex_pos)),
                 Act::MoveAct(nex_pos) => {
@@ -154,7 +163,9 @@ pub fn act_list(acts_: &Vec<Vec<Act>>, field: &field::Field) -> Vec<(bool, usize
         }
     }
     let mut que = VecDeque::new();
-    for k in act_map.keys() {
+    let mut keys = act_map.keys().collect::<Vec<_>>();
+    keys.sort();
+    for k in keys {
         if !pos_map.contains(k) {
             que.push_back(k.clone());
         }
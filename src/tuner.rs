@@ -0,0 +1,309 @@
+use crate::algorithms::{SocialDistance, Solver};
+use crate::api::parse;
+use crate::manager;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::str::FromStr;
+
+const GAMES: usize = 6;
+// 試行同士の得点を比べられるよう, どの試行も同じ盤面で評価する
+const SEED: u64 = 31;
+const POPULATION: usize = 8;
+const PARENTS: usize = 4;
+const INIT_SIGMA: f64 = 0.3;
+const MIN_SIGMA: f64 = 0.02;
+const SIGMA_LEARNING_RATE: f64 = 0.5;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Method {
+    Random,
+    Cmaes,
+}
+
+impl FromStr for Method {
+    type Err = String;
+    fn from_str(name: &str) -> Result<Method, String> {
+        match name {
+            "random" => Ok(Method::Random),
+            "cmaes" => Ok(Method::Cmaes),
+            _ => Err(format!("unknown method {}", name)),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Trial {
+    pub trial: usize,
+    pub params: Map<String, Value>,
+    pub seeds: Vec<u64>,
+    pub win: usize,
+    pub draw: usize,
+    pub loss: usize,
+    pub score: i16,
+}
+
+// 範囲指定 ([lo, hi]) の値を [0, 1] に正規化した空間で探索する
+// スケジュールの場合は phases の各要素と points の値の部分に範囲を書ける
+pub struct Space {
+    template: Value,
+    pointers: Vec<String>,
+    ranges: Vec<(f64, f64)>,
+}
+
+fn as_range(v: &Value) -> Option<(f64, f64)> {
+    let range = v.as_array()?;
+    if range.len() != 2 {
+        return None;
+    }
+    Some((range[0].as_f64()?, range[1].as_f64()?))
+}
+
+impl Space {
+    pub fn read(path: &str) -> Result<Space, String> {
+        let fp = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+        let template: Map<String, Value> =
+            serde_json::from_reader(fp).map_err(|e| format!("{}: {}", path, e))?;
+        let mut space = Space {
+            template: Value::Object(template.clone()),
+            pointers: Vec::new(),
+            ranges: Vec::new(),
+        };
+        for (k, v) in &template {
+            let mut cand = vec![(format!("/{}", k), v)];
+            if let Some(phases) = v["phases"].as_array() {
+                cand = phases
+                    .iter()
+                    .enumerate()
+                    .map(|(i, x)| (format!("/{}/phases/{}", k, i), x))
+                    .collect();
+            } else if let Some(points) = v["points"].as_array() {
+                cand = points
+                    .iter()
+                    .enumerate()
+                    .map(|(i, x)| (format!("/{}/points/{}/1", k, i), &x[1]))
+                    .collect();
+            }
+            for (pointer, x) in cand {
+                if let Some(range) = as_range(x) {
+                    space.pointers.push(pointer);
+                    space.ranges.push(range);
+                }
+            }
+        }
+        // 範囲の中央を入れたパラメータで, 試行を始める前に誤りを報告する
+        let center = space.params(&vec![0.5; space.dim()])?;
+        parse::param_schedule_from_value(Value::Object(center))
+            .map_err(|e| format!("{}: {}", path, e))?;
+        Ok(space)
+    }
+    pub fn dim(&self) -> usize {
+        self.pointers.len()
+    }
+    pub fn params(&self, x: &[f64]) -> Result<Map<String, Value>, String> {
+        let mut val = self.template.clone();
+        for ((pointer, (lo, hi)), v) in self.pointers.iter().zip(&self.ranges).zip(x) {
+            let target = val
+                .pointer_mut(pointer)
+                .ok_or_else(|| format!("{} not found", pointer))?;
+            *target = Value::from(lo + (hi - lo) * v);
+        }
+        match val {
+            Value::Object(map) => Ok(map),
+            _ => unreachable!(),
+        }
+    }
+    pub fn normalize(&self, map: &Map<String, Value>) -> Vec<f64> {
+        let val = Value::Object(map.clone());
+        self.pointers
+            .iter()
+            .zip(&self.ranges)
+            .map(|(pointer, (lo, hi))| {
+                // 古い試行ファイルに無いパラメータは範囲の中央とみなす
+                match val.pointer(pointer).and_then(|x| x.as_f64()) {
+                    Some(v) if hi != lo => ((v - lo) / (hi - lo)).clamp(0.0, 1.0),
+                    _ => 0.5,
+                }
+            })
+            .collect()
+    }
+}
+
+// 対角共分散のみを適応させる簡易的な CMA-ES
+struct Strategy {
+    mean: Vec<f64>,
+    sigma: Vec<f64>,
+}
+
+impl Strategy {
+    fn new(dim: usize) -> Strategy {
+        Strategy {
+            mean: vec![0.5; dim],
+            sigma: vec![INIT_SIGMA; dim],
+        }
+    }
+    fn sample<R: Rng>(&self, rng: &mut R) -> Vec<f64> {
+        self.mean
+            .iter()
+            .zip(&self.sigma)
+            .map(|(m, s)| (m + s * gaussian(rng)).clamp(0.0, 1.0))
+            .collect()
+    }
+    fn update(&mut self, batch: &[(Vec<f64>, i16)]) {
+        let mut sorted = batch.to_vec();
+        sorted.sort_by_key(|x| std::cmp::Reverse(x.1));
+        let mu = PARENTS.min(sorted.len());
+        let weights = (0..mu)
+            .map(|i| (mu as f64 + 0.5).ln() - (i as f64 + 1.0).ln())
+            .collect::<Vec<_>>();
+        let weight_sum = weights.iter().sum::<f64>();
+
+        for d in 0..self.mean.len() {
+            let mean = (0..mu).fold(0.0, |b, i| b + weights[i] * sorted[i].0[d]) / weight_sum;
+            let var = (0..mu).fold(0.0, |b, i| {
+                b + weights[i] * (sorted[i].0[d] - self.mean[d]).powi(2)
+            }) / weight_sum;
+            self.sigma[d] = ((1.0 - SIGMA_LEARNING_RATE) * self.sigma[d].powi(2)
+                + SIGMA_LEARNING_RATE * var)
+                .sqrt()
+                .max(MIN_SIGMA);
+            self.mean[d] = mean;
+        }
+    }
+}
+
+fn gaussian<R: Rng>(rng: &mut R) -> f64 {
+    let u = 1.0 - rng.gen::<f64>();
+    let v = rng.gen::<f64>();
+    (-2.0 * u.ln()).sqrt() * (2.0 * std::f64::consts::PI * v).cos()
+}
+
+// 書き込みの途中で止まって壊れた行などは, 警告を出して読み飛ばす
+fn read_trials(path: &str) -> Result<Vec<Trial>, String> {
+    let fp = match File::open(path) {
+        Ok(fp) => fp,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("{}: {}", path, e)),
+    };
+    let mut res = Vec::new();
+    for (i, line) in BufReader::new(fp).lines().enumerate() {
+        match line.map_err(|e| e.to_string()).and_then(|line| {
+            if line.trim().is_empty() {
+                Ok(None)
+            } else {
+                serde_json::from_str(&line)
+                    .map(Some)
+                    .map_err(|e| e.to_string())
+            }
+        }) {
+            Ok(Some(trial)) => res.push(trial),
+            Ok(None) => {}
+            Err(e) => eprintln!("WARN: {}:{}: skipped ({})", path, i + 1, e),
+        }
+    }
+    Ok(res)
+}
+
+// 最後の行が改行で終わっていなければ, 追記する前に改行を足す
+fn ends_with_newline(path: &str) -> bool {
+    fs::read(path).map_or(true, |b| b.last().map_or(true, |c| *c == b'\n'))
+}
+
+fn evaluate(trial: usize, map: Map<String, Value>, seeds: &[u64]) -> Result<Trial, String> {
+    let params = parse::param_schedule_from_value(Value::Object(map.clone()))?;
+    let ours = |side| -> Box<dyn Solver> {
+        let mut solver = SocialDistance::new(side);
+        solver.set_schedule(params.clone());
+        Box::new(solver)
+    };
+    let opponent = |side| -> Box<dyn Solver> { Box::new(SocialDistance::new(side)) };
+    let games = manager::evaluate(&ours, &opponent, seeds);
+    let report = manager::SimulateReport::new("social_distance", "social_distance", None, games);
+    Ok(Trial {
+        trial,
+        params: map,
+        seeds: seeds.to_vec(),
+        win: report.win,
+        draw: report.draw,
+        loss: report.loss,
+        score: report.diff_sum,
+    })
+}
+
+pub fn run(
+    space_path: &str,
+    trials_path: &str,
+    method: Method,
+    num_trials: usize,
+) -> Result<(), String> {
+    let space = Space::read(space_path)?;
+    let mut trials = read_trials(trials_path)?;
+    let mut strategy = Strategy::new(space.dim());
+    let mut batch = Vec::new();
+
+    // 保存済みの試行を世代ごとに流し直して探索状態を復元する
+    for trial in &trials {
+        batch.push((space.normalize(&trial.params), trial.score));
+        if batch.len() == POPULATION {
+            strategy.update(&batch);
+            batch.clear();
+        }
+    }
+    if !trials.is_empty() {
+        println!("resume from {} trials", trials.len());
+    }
+
+    let write_err = |e: std::io::Error| format!("{}: {}", trials_path, e);
+    let newline = !ends_with_newline(trials_path);
+    let mut fp = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(trials_path)
+        .map_err(write_err)?;
+    if newline {
+        writeln!(fp).map_err(write_err)?;
+    }
+    let mut rng = rand::thread_rng();
+    let mut seed_rng = StdRng::seed_from_u64(SEED);
+    let seeds = (0..GAMES)
+        .map(|_| seed_rng.gen::<u64>())
+        .collect::<Vec<_>>();
+
+    while trials.len() < num_trials {
+        let x = match method {
+            Method::Random => (0..space.dim()).map(|_| rng.gen::<f64>()).collect(),
+            Method::Cmaes => strategy.sample(&mut rng),
+        };
+        let trial = evaluate(trials.len(), space.params(&x)?, &seeds)?;
+        writeln!(
+            fp,
+            "{}",
+            serde_json::to_string(&trial).expect("trial serialize error")
+        )
+        .map_err(write_err)?;
+        fp.flush().map_err(write_err)?;
+        println!(
+            "trial {}: {} ({}W {}D {}L)",
+            trial.trial, trial.score, trial.win, trial.draw, trial.loss
+        );
+
+        batch.push((x, trial.score));
+        if batch.len() == POPULATION {
+            strategy.update(&batch);
+            batch.clear();
+        }
+        trials.push(trial);
+    }
+
+    if let Some(best) = trials.iter().max_by_key(|t| t.score) {
+        println!("best trial {}: {}", best.trial, best.score);
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&best.params).expect("params serialize error")
+        );
+    }
+    Ok(())
+}
@@ -7,19 +7,31 @@ use druid::{Data, RenderContext};
 use druid::{Env, Event, EventCtx};
 
 use crate::algorithms;
-use crate::algorithms::Solver;
+use crate::algorithms::influence::Influence;
+use crate::algorithms::registry;
+use crate::algorithms::validate;
+use crate::algorithms::{SolveContext, Solver};
 use crate::api::{parse, request};
 use crate::field;
+use crate::judge::MatchResult;
+use crate::record::GameRecord;
 use crate::simulator;
 use crate::simulator::Simulator;
 use druid::widget::Flex;
 use piet::{FontBuilder, Text, TextLayoutBuilder};
+use rand::Rng;
+use std::cell::RefCell;
+use std::rc::Rc;
 
 const MARGIN: f64 = 0.15;
 const LINE_WIDTH: f64 = 0.01;
 const ACTIVE_LINE_WIDTH: f64 = 0.03;
 const ACT_LINE_WIDTH: f64 = 0.03;
 const FONT_SIZE: f64 = 0.5;
+// 影響範囲を表示するときに何ターン先まで見るか
+const INFLUENCE_HORIZON: usize = 4;
+const INFLUENCE_ALPHA: f64 = 120.0;
+const CONTEST_LINE_WIDTH: f64 = 0.08;
 
 const BG_COLOR: druid::Color = druid::Color::rgb8(245, 245, 220);
 const GRID_COLOR: druid::Color = druid::Color::rgb8(0, 0, 0);
@@ -78,47 +90,110 @@ pub struct AppData {
     pub match_data: Option<parse::MatchData>,
     pub team_data: Option<parse::TeamData>,
     pub team_data_idx: usize,
+    #[data(ignore)]
+    pub record: Option<GameRecord>,
+    // 試合中はターンをまたいで同じソルバーを使う
+    #[data(ignore)]
+    pub solver: Option<Rc<RefCell<Box<dyn Solver>>>>,
+    // 最後に solver.update に渡したターン (同じターンを二度渡さないため)
+    #[data(ignore)]
+    pub solver_turn: Option<u8>,
+    // 各マスをどちらのチームが先に取れそうか (色) と取り合いになりそうか (枠) を重ねて表示する
+    pub show_influence: bool,
 }
 
-macro_rules! make_button {
-    ($f: ident) => {
-        (|flex: &mut Flex<_>, side: bool| {
-            let typename = std::any::type_name::<algorithms::$f>();
-            let pos = typename.to_string().rfind("::");
-            let text = &typename[(if pos.is_none() { 0 } else { pos.unwrap() + 2 })..];
-            flex.add_flex_child(
-                druid::widget::Button::new(text).on_click(move |_ctx, data: &mut AppData, _env| {
-                    let field: &field::Field = data.simulator.get_field();
-                    let mut solver = algorithms::$f::new(side, field);
-                    let res = solver.solve();
-                    for id in 0..field.agent_count() {
-                        data.simulator.set_act(side, id, res[id].clone());
-                    }
-                    let acts = data.simulator.get_acts(data.team_data_idx);
-                    if data.team_data.is_none() || data.match_data.is_none() {
-                        println!("team_data / match_data is none")
-                    } else {
-                        request::send_act(
-                            acts,
-                            &data.team_data.as_ref().unwrap(),
-                            &data.match_data.as_ref().unwrap(),
-                            &data.config,
-                        );
-                    }
-                }),
-                1.0,
-            );
-            flex.add_spacer(10.);
-        })
-    };
+fn solve_context(data: &AppData) -> SolveContext<'_> {
+    let history = data.record.as_ref().map_or(&[][..], |r| &r.turns[..]);
+    SolveContext::new(data.config.turn_sec, rand::thread_rng().gen(), history)
+}
+
+// ソルバーの手を送信前に確かめて修正する
+fn validated(side: bool, acts: &[simulator::Act], field: &field::Field) -> Vec<simulator::Act> {
+    let report = validate::validate(side, acts, field);
+    for (id, act, issue) in &report.issues {
+        println!("WARN: agent {} {:?} {:?}", id, act, issue);
+    }
+    report.acts
+}
+
+// 前のターンの結果を渡してから使う. 試合の最初のターンなら新しく作る
+fn match_solver(data: &mut AppData, side: bool) -> Result<Rc<RefCell<Box<dyn Solver>>>, String> {
+    let last = data.record.as_ref().and_then(|r| r.turns.last());
+    match &data.solver {
+        Some(solver) => {
+            if let Some(turn) = last.filter(|t| data.solver_turn < Some(t.turn)) {
+                solver
+                    .borrow_mut()
+                    .update(data.simulator.get_field(), &turn.acts);
+                data.solver_turn = Some(turn.turn);
+            }
+            Ok(solver.clone())
+        }
+        None => {
+            let solver = Rc::new(RefCell::new(algorithms::make_solver(
+                &data.config.solver,
+                side,
+            )?));
+            data.solver_turn = last.map(|t| t.turn);
+            data.solver = Some(solver.clone());
+            Ok(solver)
+        }
+    }
+}
+
+fn make_button(flex: &mut Flex<AppData>, side: bool, name: &'static str) {
+    flex.add_flex_child(
+        druid::widget::Button::new(name).on_click(move |_ctx, data: &mut AppData, _env| {
+            let field: &field::Field = data.simulator.get_field();
+            let mut solver = match algorithms::make_solver(name, side) {
+                Ok(solver) => solver,
+                Err(e) => {
+                    println!("ERROR: {}", e);
+                    return;
+                }
+            };
+            let res = solver.solve(field, &mut solve_context(data));
+            if let Some(explanation) = solver.explain() {
+                println!("{}", explanation);
+            }
+            let res = validated(side, &res, field);
+            for id in 0..field.agent_count() {
+                data.simulator.set_act(side, id, res[id].clone());
+            }
+            let acts = data.simulator.get_acts(data.team_data_idx);
+            if data.team_data.is_none() || data.match_data.is_none() {
+                println!("team_data / match_data is none")
+            } else {
+                request::send_act(
+                    acts,
+                    &data.team_data.as_ref().unwrap(),
+                    &data.match_data.as_ref().unwrap(),
+                    &data.config,
+                );
+            }
+        }),
+        1.0,
+    );
+    flex.add_spacer(10.);
 }
 
 fn make_side_ui(side: bool) -> impl Widget<AppData> {
     let mut flex = Flex::column().must_fill_main_axis(true);
-    make_button!(GreedySelect)(&mut flex, side);
-    make_button!(SimpleDp)(&mut flex, side);
-    make_button!(SimpleRegret)(&mut flex, side);
-    make_button!(SocialDistance)(&mut flex, side);
+    for name in registry::names() {
+        make_button(&mut flex, side, name);
+    }
+
+    if side {
+        flex.add_spacer(20.);
+        flex.add_flex_child(
+            druid::widget::Button::new("Influence").on_click(
+                move |_ctx, data: &mut AppData, _env| {
+                    data.show_influence = !data.show_influence;
+                },
+            ),
+            1.0,
+        );
+    }
 
     if !side {
         flex.add_spacer(20.);
@@ -128,6 +203,11 @@ fn make_side_ui(side: bool) -> impl Widget<AppData> {
                     Ok(res) => {
                         assert_eq!(res.len(), 1);
                         println!("{:?}", res[0]);
+                        if data.match_data.as_ref().map(|m| m.match_id) != Some(res[0].match_id) {
+                            data.record = None;
+                            data.solver = None;
+                            data.solver_turn = None;
+                        }
                         data.match_data = Some(res[0].clone());
                         data.team_data_idx = if res[0].teams[0].team_id as usize == data.config.id {
                             0
@@ -161,10 +241,39 @@ fn make_side_ui(side: bool) -> impl Widget<AppData> {
                                     data.simulator.set_field(&res.field);
                                     data.simulator.reset_acts();
 
+                                    match &mut data.record {
+                                        Some(record) => record.push_field(&res.field),
+                                        None => {
+                                            data.record = Some(GameRecord::new(
+                                                data.team_data_idx == 1,
+                                                &res.field,
+                                                None,
+                                            ))
+                                        }
+                                    }
+                                    let path = format!(
+                                        "./data/records/match_{}.json",
+                                        match_data.match_id
+                                    );
+                                    if let Err(e) = data.record.as_ref().unwrap().save(&path) {
+                                        println!("ERROR: {}", e);
+                                    }
+
                                     let side = data.team_data_idx == 1;
+                                    let solver = match match_solver(data, side) {
+                                        Ok(solver) => solver,
+                                        Err(e) => {
+                                            println!("ERROR: {}", e);
+                                            return;
+                                        }
+                                    };
                                     let field: &field::Field = data.simulator.get_field();
-                                    let mut solver = algorithms::SocialDistance::new(side, field);
-                                    let res = solver.solve();
+                                    let res =
+                                        solver.borrow_mut().solve(field, &mut solve_context(data));
+                                    if let Some(explanation) = solver.borrow().explain() {
+                                        println!("{}", explanation);
+                                    }
+                                    let res = validated(side, &res, field);
                                     for id in 0..field.agent_count() {
                                         data.simulator.set_act(side, id, res[id].clone());
                                     }
@@ -402,6 +511,11 @@ impl Widget<AppData> for GameWidget {
             paint_ctx.fill(field_rect, get_color(ColorData::Field))
         });
 
+        let influence = if data.show_influence {
+            Some(Influence::new(field, INFLUENCE_HORIZON))
+        } else {
+            None
+        };
         for i in 0..field.width() {
             for j in 0..field.height() {
                 let rect = self.tile_to_vis(i, j, field);
@@ -409,6 +523,24 @@ impl Widget<AppData> for GameWidget {
                 paint_ctx.paint_with_z_index(3, move |paint_ctx| {
                     paint_ctx.fill(rect, get_color(ColorData::Tile(tile)))
                 });
+                if let Some(influence) = &influence {
+                    let pos = field::PointUsize::new(i, j);
+                    let (red, blue) = (influence.control(false, pos), influence.control(true, pos));
+                    let color = Color::rgba8(
+                        (255.0 * red) as u8,
+                        0,
+                        (255.0 * blue) as u8,
+                        (INFLUENCE_ALPHA * red.max(blue)) as u8,
+                    );
+                    paint_ctx.paint_with_z_index(3, move |paint_ctx| paint_ctx.fill(rect, &color));
+                    let width = self.grid_size * CONTEST_LINE_WIDTH * influence.contest(pos);
+                    if width > 0.0 {
+                        let inner = rect.inset(-width / 2.0);
+                        paint_ctx.paint_with_z_index(4, move |paint_ctx| {
+                            paint_ctx.stroke(inner, &Color::rgb8(255, 160, 0), width)
+                        });
+                    }
+                }
                 let width = self.grid_size * LINE_WIDTH;
                 paint_ctx.paint_with_z_index(4, move |paint_ctx| {
                     paint_ctx.stroke(rect, get_color(ColorData::Grid), width)
@@ -490,7 +622,21 @@ impl Widget<AppData> for GameWidget {
                 paint_ctx.draw_text(&layout, pos, get_color(ColorData::Agent(side)));
             });
         }
-        let turn_str = &format!("{}/{}", field.now_turn(), field.final_turn());
+        let turn_str = &if field.now_turn() == field.final_turn() {
+            let result = MatchResult::from_field(field);
+            match result.winner() {
+                Some(side) => format!(
+                    "{}/{} {} win ({:?})",
+                    field.now_turn(),
+                    field.final_turn(),
+                    if side { "blue" } else { "red" },
+                    result.criterion()
+                ),
+                None => format!("{}/{} draw", field.now_turn(), field.final_turn()),
+            }
+        } else {
+            format!("{}/{}", field.now_turn(), field.final_turn())
+        };
         let text = paint_ctx.render_ctx.text();
         let font = text
             .new_font_by_name("Segoe UI", self.grid_size * FONT_SIZE)